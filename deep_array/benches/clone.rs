@@ -1,9 +1,9 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use deep_array::Array;
+use deep_array::*;
 
 fn criterion_benchmark(c: &mut Criterion) {
     println!("benchmarking");
-    let original: Array<usize, 3> = Array::fill(1, [10, 10, 10]);
+    let original: Array3<usize> = Array3::fill(1, &[10, 10, 10]);
     println!("benchmarking");
     c.bench_function("Clone usize 10x10x10", |b| {
         b.iter(|| black_box(original.clone()))
@@ -38,11 +38,11 @@ impl<T, const N: usize> Array<T, N> for BaseArray<T, N> {
     }
 
     fn shape(&self) -> [usize; N] {
-        self.shape.clone()
+        self.shape
     }
 
     fn stride(&self) -> [usize; N] {
-        self.stride.clone()
+        self.stride
     }
 }
 
@@ -52,20 +52,20 @@ impl<T, const N: usize> Array<T, N> for BorrowedArray<T, N> {
     }
 
     fn shape(&self) -> [usize; N] {
-        self.shape.clone()
+        self.shape
     }
 
     fn stride(&self) -> [usize; N] {
-        self.stride.clone()
+        self.stride
     }
 }
 
 impl<T: Clone, const N: usize> Initialize<T, N> for BaseArray<T, N> {
     unsafe fn from_raw_parts(ptr: *mut T, stride: [usize; N], shape: [usize; N]) -> Self {
         Self {
-            ptr: ptr,
-            stride: stride,
-            shape: shape,
+            ptr,
+            stride,
+            shape,
         }
     }
 }
@@ -4,10 +4,9 @@
 //! Users can define their own array types by implementing [Array].
 
 use crate::allocation::stride_packed;
-use crate::array::{BaseArray, BorrowedArray};
+use crate::array::BorrowedArray;
 use crate::error::Error;
 use std::alloc::{alloc, Layout};
-use std::fmt;
 
 /// Trait defining core Array behaviour
 pub trait Array<T, const N: usize> {
@@ -32,7 +31,7 @@ pub trait Array<T, const N: usize> {
             unsafe { Ok(self._get_unchecked(offset)) }
         } else {
             Err(Error::OffsetOutOfBounds {
-                offset: offset,
+                offset,
                 bound: self.size(),
             })
         }
@@ -67,11 +66,11 @@ pub trait Array<T, const N: usize> {
     /// an error
     fn _get_internal_ix(&self, ix: &[usize; N]) -> Result<usize, Error> {
         let mut internal_ix = 0;
-        for (axis_ix, (ix, axis_size)) in ix.zip(self.shape()).iter().enumerate() {
+        for (axis_ix, (ix, axis_size)) in ix.iter().zip(self.shape().iter()).enumerate() {
             if ix >= axis_size {
                 return Err(Error::IndexOutOfBounds {
                     ix: *ix,
-                    axis_ix: axis_ix,
+                    axis_ix,
                     axis_size: *axis_size,
                 });
             } else {
@@ -92,7 +91,7 @@ pub trait Array<T, const N: usize> {
             unsafe { Ok(self._get_mut_unchecked(offset)) }
         } else {
             Err(Error::OffsetOutOfBounds {
-                offset: offset,
+                offset,
                 bound: self.size(),
             })
         }
@@ -116,9 +115,9 @@ pub trait Array<T, const N: usize> {
     /// # }
     /// ```
     fn get(&self, ix: &[usize; N]) -> Result<&T, Error> {
-        let internal_ix = self._get_internal_ix(&ix)?;
+        let internal_ix = self._get_internal_ix(ix)?;
         // Safe because internal_ix is boundary checked
-        return unsafe { Ok(self._get_unchecked(internal_ix)) };
+        unsafe { Ok(self._get_unchecked(internal_ix)) }
     }
 
     /// Return a mutable reference to an element at an Index or an Error if the index is out of bounds
@@ -140,9 +139,9 @@ pub trait Array<T, const N: usize> {
     /// # }
     /// ```
     fn get_mut(&mut self, ix: &[usize; N]) -> Result<&mut T, Error> {
-        let internal_ix = self._get_internal_ix(&ix)?;
+        let internal_ix = self._get_internal_ix(ix)?;
         // Safe because internal_ix is boundary checked
-        return unsafe { Ok(self._get_mut_unchecked(internal_ix)) };
+        unsafe { Ok(self._get_mut_unchecked(internal_ix)) }
     }
 
     /// Get the number of elements in the array
@@ -221,9 +220,9 @@ pub trait Initialize<T: Clone, const N: usize>: Array<T, N> + Sized {
     /// cause undefined behaviour.
     unsafe fn uninitialized(shape: &[usize; N]) -> Self {
         let layout = Layout::array::<T>(shape.iter().product()).unwrap();
-        let ptr = alloc(layout.clone()) as *mut T;
+        let ptr = alloc(layout) as *mut T;
 
-        Self::from_raw_parts(ptr, stride_packed(&shape, std::mem::size_of::<T>()), shape.to_owned())
+        Self::from_raw_parts(ptr, stride_packed(shape, std::mem::size_of::<T>()), shape.to_owned())
     }
 
     /// Create a new instance of [Array] where every element is a clone of item.
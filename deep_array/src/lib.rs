@@ -1,6 +1,5 @@
 //! deep_array provides the [Array] type, which is a n-dimensional array.
 #![deny(missing_docs)]
-#![feature(array_zip)]
 
 pub mod allocation;
 pub mod array;
@@ -1,18 +1,25 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use deep_thought::prelude::*;
 use ndarray::Array2;
 use ndarray_rand::{rand_distr::Uniform, RandomExt};
-use rust_nn::neural_network::{Layer, NeuralNetworkBuilder};
+
+// (50 + 1) * 20 + (20 + 1) * 10
+const NUM_PARAMETERS: usize = 1230;
 
 fn criterion_benchmark(c: &mut Criterion) {
     // Build the neural net
-    let mut net = NeuralNetworkBuilder::new()
-        .add_layer(Layer::new(50, 100))
-        .add_layer(Layer::new(100, 10));
+    let net = NeuralNetwork::<f64, NUM_PARAMETERS>::new()
+        .add_layer(Layer::new(50, 20).activation(Activation::Sigmoid))
+        .add_layer(Layer::new(20, 10));
 
     // construct some arbitrary input of 10 batches
     let inp = Array2::random((50, 10), Uniform::new(-1., 1.));
+    let dual_inp = inp.map(|&x| Dual::constant(x));
 
-    c.bench_function("Forward pass", |b| b.iter(|| net.forward(black_box(&inp))));
+    let mut group = c.benchmark_group("Forward pass");
+    group.bench_function("dual", |b| b.iter(|| net.forward(black_box(&dual_inp))));
+    group.bench_function("predict", |b| b.iter(|| net.predict(black_box(&inp))));
+    group.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -1,6 +1,8 @@
-use crate::autograd::Dual;
+use crate::autograd::{Dual, DynDual, HyperDual, SparseTangents, Tangents, Var};
+use crate::error::Error;
 use ndarray::prelude::*;
-use num_traits::{Float, One, Zero};
+use num_traits::{Float, NumCast};
+use std::convert::TryFrom;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -8,47 +10,567 @@ use serde::{Deserialize, Serialize};
 /// Possible activation functions to apply on a Layer's Z value
 /// Each Activation function must be continuous and differentiable
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub enum Activation<F, const N: usize> {
+#[derive(Default)]
+pub enum Activation<F, const N: usize = 0> {
     /// values < 0 become 0
     ReLU,
     /// no changes, f(x) = x
+    #[default]
     Linear,
     /// squash every input into a range between 0 and 1
     Sigmoid,
     /// Values < 0 get scaled down by a lot. Similar to ReLU except gradients don't become 0. LeakyReLu(0) = ReLU
-    LeakyReLU(Dual<F, N>),
-    /// Sum of all output values is 1. Useful for getting a probability distribution over the action space
-    Softmax,
+    LeakyReLU(F),
+    /// Sum of all output values along `axis` is 1. Useful for getting a probability distribution over the action space.
+    /// [`SoftmaxAxis::Features`] normalizes every sample on its own
+    Softmax { axis: SoftmaxAxis },
+    /// Logarithm of Softmax, computed in a numerically stable way.
+    /// Pairs with a negative log-likelihood loss
+    LogSoftmax { axis: SoftmaxAxis },
     /// Sqash every input into a range between -1 and 1
     Tanh,
+    /// Gaussian Error Linear Unit, x * Φ(x) where Φ is the standard normal CDF
+    GELU,
+    /// Approximation of GELU using tanh, as used by many transformer models
+    GELUTanh,
+    /// Sigmoid Linear Unit (also known as Swish), x * sigmoid(x)
+    SiLU,
+    /// Exponential Linear Unit, values < 0 approach -alpha smoothly
+    ELU(F),
+    /// Scaled ELU with fixed constants that keep activations normalized
+    SELU,
+    /// Smooth approximation of ReLU, ln(1 + e^x)
+    Softplus,
+    /// Squash every input into a range between -1 and 1, approaching the bounds slower than Tanh
+    Softsign,
+    /// x * tanh(softplus(x))
+    Mish,
+    /// Clamp every input into a range between -1 and 1
+    HardTanh,
+    /// Piecewise linear approximation of Sigmoid, x / 6 + 1/2 clamped to a range between 0 and 1
+    HardSigmoid,
 }
 
-impl<F: Float, const N: usize> Activation<F, N> {
-    /// compute the result of this activation function for a given input (forward propagate)
-    pub fn compute(&self, inp: &Array2<Dual<F, N>>) -> Array2<Dual<F, N>> {
+/// Axis along which [`Activation::Softmax`] and [`Activation::LogSoftmax`] normalize
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoftmaxAxis {
+    /// Normalize the features of every sample (column) on their own
+    Features,
+    /// Normalize every feature (row) over the samples of the batch
+    Batch,
+}
+
+impl SoftmaxAxis {
+    /// Axis of a features x batch array whose lanes are normalized
+    fn axis(self) -> Axis {
+        match self {
+            SoftmaxAxis::Features => Axis(0),
+            SoftmaxAxis::Batch => Axis(1),
+        }
+    }
+}
+
+impl TryFrom<usize> for SoftmaxAxis {
+    type Error = Error;
+
+    /// Convert an axis index of a features x batch array, e.g. read from a configuration file
+    fn try_from(axis: usize) -> Result<Self, Error> {
+        match axis {
+            0 => Ok(SoftmaxAxis::Features),
+            1 => Ok(SoftmaxAxis::Batch),
+            _ => Err(Error::InvalidAxis { axis, ndim: 2 }),
+        }
+    }
+}
+
+/// An activation function applied to a Layer's Z value of number type `D`, which holds one
+/// sample per column.
+///
+/// Implement this generically over `D` to use activation functions that are not part of
+/// [`Activation`]. They are written in terms of [`Float`] methods, so derivatives come for free
+/// and the activation works with every [`Engine`](crate::neural_network::Engine) (see
+/// [`ActivationFn`]):
+/// ```
+/// # use deep_thought::prelude::*;
+/// # use ndarray::prelude::*;
+/// # use num_traits::Float;
+/// /// x * sigmoid(x)
+/// struct Swish;
+///
+/// impl<D: Float + From<f64>> ActivationOn<f64, D> for Swish {
+///     fn compute(&self, inp: &Array2<D>) -> Array2<D> {
+///         inp.map(|&x| x / (D::one() + (-x).exp()))
+///     }
+/// }
+///
+/// let net = NeuralNetwork::<f64, 0, _>::new()
+///     .add_layer(Layer::new(2, 3).activation(Swish))
+///     .add_layer(Layer::new(3, 1).activation(Swish));
+/// ```
+pub trait ActivationOn<F: Float, D: Float + From<F>> {
+    /// compute the result of this activation function for a given input (forward propagate).
+    ///
+    /// The input may consist of any number type that can represent the network's parameters,
+    /// like [`Dual`] or [`DynDual`].
+    fn compute(&self, inp: &Array2<D>) -> Array2<D>;
+}
+
+impl<F: Float, D: Float + From<F>, A: ActivationOn<F, D> + ?Sized> ActivationOn<F, D> for Box<A> {
+    fn compute(&self, inp: &Array2<D>) -> Array2<D> {
+        (**self).compute(inp)
+    }
+}
+
+/// An activation function for networks tracking up to `N` parameters, which works with plain
+/// floats and the number type of every [`Engine`](crate::neural_network::Engine).
+///
+/// Implemented for every type implementing [`ActivationOn`] for all of these number types, which
+/// is the case for [`Activation`] and every [`ActivationOn`] implemented generically. `N` is only
+/// needed to name [`Dual<F, N>`] and [`HyperDual<F, N>`], so the trait can be used as a trait
+/// object to combine custom and built-in functions within one network:
+/// ```
+/// # use deep_thought::prelude::*;
+/// # use ndarray::prelude::*;
+/// # use num_traits::Float;
+/// struct Swish;
+///
+/// impl<D: Float + From<f64>> ActivationOn<f64, D> for Swish {
+///     fn compute(&self, inp: &Array2<D>) -> Array2<D> {
+///         inp.map(|&x| x / (D::one() + (-x).exp()))
+///     }
+/// }
+///
+/// let net = NeuralNetwork::<f64, 0, Box<dyn ActivationFn<f64, 0>>>::new()
+///     .add_layer(Layer::new(2, 3).activation(Box::new(Swish)))
+///     .add_layer(Layer::new(3, 1).activation(Box::new(Activation::<f64, 0>::Sigmoid)));
+/// ```
+pub trait ActivationFn<F: Float, const N: usize>:
+    ActivationOn<F, F>
+    + ActivationOn<F, Dual<F, N>>
+    + ActivationOn<F, HyperDual<F, N>>
+    + for<'t> ActivationOn<F, DynDual<'t, F, Tangents<F>>>
+    + for<'t> ActivationOn<F, DynDual<'t, F, SparseTangents<F>>>
+    + for<'t> ActivationOn<F, Var<'t, F>>
+{
+}
+
+impl<F: Float, const N: usize, A: ?Sized> ActivationFn<F, N> for A where
+    A: ActivationOn<F, F>
+        + ActivationOn<F, Dual<F, N>>
+        + ActivationOn<F, HyperDual<F, N>>
+        + for<'t> ActivationOn<F, DynDual<'t, F, Tangents<F>>>
+        + for<'t> ActivationOn<F, DynDual<'t, F, SparseTangents<F>>>
+        + for<'t> ActivationOn<F, Var<'t, F>>
+{
+}
+
+impl<F: Float, const N: usize, D: Float + From<F>> ActivationOn<F, D> for Activation<F, N> {
+    fn compute(&self, inp: &Array2<D>) -> Array2<D> {
         match &self {
-            Activation::ReLU => inp.map(|&x| if x > Dual::zero() { x } else { Dual::zero() }),
+            Activation::ReLU => inp.map(|&x| if x > D::zero() { x } else { D::zero() }),
             Activation::Linear => inp.clone(),
-            Activation::Sigmoid => {
-                inp.map(|x| Dual::<F, N>::one() / (Dual::<F, N>::one() + (-x).exp()))
-            }
+            Activation::Sigmoid => inp.map(|&x| D::one() / (D::one() + (-x).exp())),
             Activation::LeakyReLU(slope) => {
-                inp.map(|&x| if x > Dual::zero() { x } else { slope * x })
+                let slope: D = (*slope).into();
+                inp.map(|&x| if x > D::zero() { x } else { slope * x })
             }
             Activation::Tanh => inp.map(|&x| x.tanh()),
-            Activation::Softmax => {
-                // shift the values by -max(inputs) to prevent overflow (does not affect derivative)
-                let max = inp.iter().max_by(|a, b| a.partial_cmp(b).unwrap()).unwrap();
-                let tmp = inp.map(|x| (x - max).exp());
-                let sum = tmp.sum();
-                tmp.map(|x| x / sum)
+            Activation::GELU => {
+                let half: D = constant(0.5);
+                let scale: D = constant(std::f64::consts::FRAC_1_SQRT_2);
+                // Φ(x) = erfc(-x / √2) / 2
+                inp.map(|&x| half * x * erfc(-x * scale))
+            }
+            Activation::GELUTanh => {
+                let half: D = constant(0.5);
+                let scale: D = constant((2. / std::f64::consts::PI).sqrt());
+                let cubic: D = constant(0.044715);
+                inp.map(|&x| half * x * (D::one() + (scale * (x + cubic * x.powi(3))).tanh()))
+            }
+            Activation::SiLU => inp.map(|&x| x / (D::one() + (-x).exp())),
+            Activation::ELU(alpha) => {
+                let alpha: D = (*alpha).into();
+                inp.map(|&x| if x > D::zero() { x } else { alpha * x.exp_m1() })
             }
+            Activation::SELU => {
+                let lambda: D = constant(1.0507009873554805);
+                let alpha: D = constant(1.6732632423543772);
+                inp.map(|&x| {
+                    if x > D::zero() {
+                        lambda * x
+                    } else {
+                        lambda * alpha * x.exp_m1()
+                    }
+                })
+            }
+            Activation::Softplus => inp.map(|&x| softplus(x)),
+            Activation::Softsign => inp.map(|&x| x / (D::one() + x.abs())),
+            Activation::Mish => inp.map(|&x| x * softplus(x).tanh()),
+            Activation::HardTanh => inp.map(|&x| x.max(-D::one()).min(D::one())),
+            Activation::HardSigmoid => {
+                let (half, sixth): (D, D) = (constant(0.5), constant(1. / 6.));
+                inp.map(|&x| (x * sixth + half).max(D::zero()).min(D::one()))
+            }
+            Activation::Softmax { axis } => log_softmax(inp, *axis).mapv(|x| x.exp()),
+            Activation::LogSoftmax { axis } => log_softmax(inp, *axis),
         }
     }
 }
 
-impl<F, const N: usize> Default for Activation<F, N> {
-    fn default() -> Activation<F, N> {
-        Activation::Linear
+/// Convert a constant into the number type used by an activation function
+fn constant<D: Float>(x: f64) -> D {
+    <D as NumCast>::from(x).unwrap()
+}
+
+/// Logarithm of the softmax of every lane along `axis`
+pub(crate) fn log_softmax<D: Float>(inp: &Array2<D>, axis: SoftmaxAxis) -> Array2<D> {
+    let mut out = inp.clone();
+    for mut lane in out.lanes_mut(axis.axis()) {
+        // shift the values by -max(lane) to prevent overflow (does not affect derivative)
+        let max = lane.iter().fold(D::neg_infinity(), |a, &b| a.max(b));
+        let log_sum = lane
+            .iter()
+            .fold(D::zero(), |a, &x| a + (x - max).exp())
+            .ln()
+            + max;
+        lane.mapv_inplace(|x| x - log_sum);
+    }
+    out
+}
+
+/// ln(1 + e^x), rearranged to prevent overflow for large inputs
+fn softplus<D: Float>(x: D) -> D {
+    x.max(D::zero()) + (-x.abs()).exp().ln_1p()
+}
+
+// Coefficients of the rational approximations from W. J. Cody, "Rational Chebyshev
+// approximations for the error function" (1969), ordered from the highest degree
+
+/// erf(x) / x in terms of x² for |x| <= 0.46875
+const ERF_NUMERATOR: [f64; 5] = [
+    0.18577770618460315,
+    3.1611237438705655,
+    113.86415415105016,
+    377.485237685302,
+    3209.3775891384694,
+];
+const ERF_DENOMINATOR: [f64; 5] = [
+    1.,
+    23.601290952344122,
+    244.02463793444417,
+    1282.6165260773723,
+    2844.236833439171,
+];
+/// erfc(x) e^(x²) for 0.46875 < x <= 4
+const ERFC_NUMERATOR: [f64; 9] = [
+    2.1531153547440383e-8,
+    0.5641884969886701,
+    8.883149794388377,
+    66.11919063714163,
+    298.6351381974001,
+    881.952221241769,
+    1712.0476126340707,
+    2051.0783778260716,
+    1230.3393547979972,
+];
+const ERFC_DENOMINATOR: [f64; 9] = [
+    1.,
+    15.744926110709835,
+    117.6939508913125,
+    537.1811018620099,
+    1621.3895745666903,
+    3290.7992357334597,
+    4362.619090143247,
+    3439.3676741437216,
+    1230.3393548037495,
+];
+/// (1/√π - x erfc(x) e^(x²)) x² in terms of 1/x² for x > 4
+const ERFC_ASYMPTOTIC_NUMERATOR: [f64; 6] = [
+    0.016315387137302097,
+    0.30532663496123236,
+    0.36034489994980445,
+    0.12578172611122926,
+    0.016083785148742275,
+    0.0006587491615298378,
+];
+const ERFC_ASYMPTOTIC_DENOMINATOR: [f64; 6] = [
+    1.,
+    2.568520192289822,
+    1.8729528499234673,
+    0.5279051029514285,
+    0.06051834131244132,
+    0.0023352049762686918,
+];
+
+/// Ratio of two polynomials at `x`, given their coefficients ordered from the highest degree
+fn rational<D: Float>(x: D, numerator: &[f64], denominator: &[f64]) -> D {
+    let horner = |coefficients: &[f64]| {
+        coefficients
+            .iter()
+            .fold(D::zero(), |acc, &c| acc * x + constant(c))
+    };
+    horner(numerator) / horner(denominator)
+}
+
+/// Complementary error function 1 - erf(x) to double precision, using Cody's rational
+/// approximations. Large inputs are computed directly instead of as 1 - erf(x), which keeps the
+/// relative precision where erf(x) rounds to one.
+fn erfc<D: Float>(x: D) -> D {
+    let y = x.abs();
+    if y <= constant(0.46875) {
+        return D::one() - x * rational(y * y, &ERF_NUMERATOR, &ERF_DENOMINATOR);
+    }
+    let tail = if y <= constant(4.) {
+        rational(y, &ERFC_NUMERATOR, &ERFC_DENOMINATOR)
+    } else {
+        let frac_1_sqrt_pi: D = constant(0.5 * std::f64::consts::FRAC_2_SQRT_PI);
+        let z = (y * y).recip();
+        let correction = z * rational(z, &ERFC_ASYMPTOTIC_NUMERATOR, &ERFC_ASYMPTOTIC_DENOMINATOR);
+        (frac_1_sqrt_pi - correction) / y
+    };
+    // e^(-y²) = e^(-r²) e^(-(y - r)(y + r)) with r = y rounded down to a multiple of 1/16,
+    // whose square is exact
+    let sixteen: D = constant(16.);
+    let r = (y * sixteen).trunc() / sixteen;
+    let erfc = (-r * r).exp() * (-(y - r) * (y + r)).exp() * tail;
+    if x < D::zero() {
+        constant::<D>(2.) - erfc
+    } else {
+        erfc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::autograd::{check_gradient, Dual};
+
+    const INPUTS: [f64; 5] = [-2., -0.5, 0., 0.7, 3.];
+
+    fn assert_reference(activation: Activation<f64, 1>, expected: [f64; 5]) {
+        let found = activation.compute(&Array2::from_shape_vec((5, 1), INPUTS.to_vec()).unwrap());
+        for (x, (a, b)) in INPUTS.iter().zip(found.iter().zip(expected.iter())) {
+            assert!((a - b).abs() < 1e-12, "f({}) = {} != {}", x, a, b);
+        }
+    }
+
+    // reference values were computed in Python from the closed-form definitions using
+    // math.erf, math.tanh and math.exp
+    #[test]
+    fn reference_values() {
+        assert_reference(
+            Activation::GELU,
+            [
+                -0.04550026389635842,
+                -0.15426876936299344,
+                0.,
+                0.5306254434438489,
+                2.99595030590511,
+            ],
+        );
+        assert_reference(
+            Activation::GELUTanh,
+            [
+                -0.04540230591222494,
+                -0.15428599017485606,
+                0.,
+                0.5305701347051167,
+                2.996362607918227,
+            ],
+        );
+        assert_reference(
+            Activation::SiLU,
+            [
+                -0.2384058440442351,
+                -0.1887703343990727,
+                0.,
+                0.46773144051771626,
+                2.8577223804672998,
+            ],
+        );
+        assert_reference(
+            Activation::ELU(0.5),
+            [-0.43233235838169365, -0.1967346701436833, 0., 0.7, 3.],
+        );
+        assert_reference(
+            Activation::SELU,
+            [
+                -1.520166468595695,
+                -0.6917581878028713,
+                0.,
+                0.7354906911488363,
+                3.1521029620664414,
+            ],
+        );
+        assert_reference(
+            Activation::Softplus,
+            [
+                0.1269280110429725,
+                0.4740769841801067,
+                std::f64::consts::LN_2,
+                1.103186048885458,
+                3.048587351573742,
+            ],
+        );
+        assert_reference(
+            Activation::Softsign,
+            [
+                -0.6666666666666666,
+                -0.3333333333333333,
+                0.,
+                0.4117647058823529,
+                0.75,
+            ],
+        );
+        assert_reference(
+            Activation::Mish,
+            [
+                -0.2525014826957089,
+                -0.22074377465173,
+                0.,
+                0.5611483776438518,
+                2.9865350049679575,
+            ],
+        );
+        assert_reference(Activation::HardTanh, [-1., -0.5, 0., 0.7, 1.]);
+        assert_reference(
+            Activation::HardSigmoid,
+            [
+                0.16666666666666669,
+                0.4166666666666667,
+                0.5,
+                0.6166666666666667,
+                1.,
+            ],
+        );
+    }
+
+    // reference values from Python's math.erfc
+    #[test]
+    fn erfc_keeps_relative_precision() {
+        let cases = [
+            (0.2, 0.7772974107895215),
+            (-3., 1.9999779095030015),
+            (5., 1.5374597944280351e-12),
+            (10., 2.088487583762545e-45),
+        ];
+        for (x, expected) in cases {
+            let found = erfc(x);
+            assert!(
+                (found - expected).abs() < 1e-14 * expected,
+                "erfc({}) = {}",
+                x,
+                found
+            );
+        }
+    }
+
+    // d/dx x Φ(x) = Φ(x) + x φ(x), reference values from Python's math.erfc and math.exp
+    #[test]
+    fn gelu_derivative_matches_closed_form() {
+        let cases = [
+            (-8., -3.979607261086796e-14),
+            (-3., -0.011945647204183927),
+            (-0.5, 0.13250487534383712),
+            (0.3, 0.7323277668271098),
+            (2., 1.085231801078197),
+            (6., 1.0000000354687093),
+        ];
+        for (x, expected) in cases {
+            let out = Activation::<f64>::GELU.compute(&array![[Dual::<f64, 1>::variable(x, 0)]]);
+            let found = out[[0, 0]].e[0];
+            assert!(
+                (found - expected).abs() < 1e-12 * expected.abs(),
+                "GELU'({}) = {} != {}",
+                x,
+                found,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn softmax_normalizes_every_sample() {
+        let inp = array![[1., 1000.], [2., 1000.], [3., 998.]];
+        let out = Activation::<f64, 0>::Softmax {
+            axis: SoftmaxAxis::Features,
+        }
+        .compute(&inp);
+
+        let e = [1f64.exp(), 2f64.exp(), 3f64.exp()];
+        let sum: f64 = e.iter().sum();
+        for (i, e) in e.iter().enumerate() {
+            assert!((out[[i, 0]] - e / sum).abs() < 1e-12);
+        }
+        // large inputs neither overflow nor affect other samples
+        let sum = 2. + (-2f64).exp();
+        let expected = [1. / sum, 1. / sum, (-2f64).exp() / sum];
+        for (i, e) in expected.iter().enumerate() {
+            assert!((out[[i, 1]] - e).abs() < 1e-12);
+        }
+        for column in out.columns() {
+            assert!((column.sum() - 1.).abs() < 1e-12);
+        }
+
+        // normalize every feature over the batch instead
+        let out = Activation::<f64, 0>::Softmax {
+            axis: SoftmaxAxis::Batch,
+        }
+        .compute(&inp);
+        for row in out.rows() {
+            assert!((row.sum() - 1.).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn log_softmax_is_logarithm_of_softmax() {
+        let inp = array![[0.5, -3.], [2., 0.], [-1., 4.]];
+        for axis in [SoftmaxAxis::Features, SoftmaxAxis::Batch] {
+            let softmax = Activation::<f64, 0>::Softmax { axis }.compute(&inp);
+            let log_softmax = Activation::<f64, 0>::LogSoftmax { axis }.compute(&inp);
+            for (a, b) in softmax.iter().zip(log_softmax.iter()) {
+                assert!((a.ln() - b).abs() < 1e-12);
+            }
+        }
+        // stays finite where softmax underflows to zero
+        let out = Activation::<f64, 0>::LogSoftmax {
+            axis: SoftmaxAxis::Features,
+        }
+        .compute(&array![[0.], [1000.]]);
+        assert_eq!(out, array![[-1000.], [0.]]);
+    }
+
+    #[test]
+    fn softmax_axis_must_exist() {
+        assert_eq!(SoftmaxAxis::try_from(0).unwrap(), SoftmaxAxis::Features);
+        assert_eq!(SoftmaxAxis::try_from(1).unwrap(), SoftmaxAxis::Batch);
+        assert!(matches!(
+            SoftmaxAxis::try_from(2),
+            Err(Error::InvalidAxis { axis: 2, ndim: 2 })
+        ));
+    }
+
+    #[test]
+    fn derivatives_match_finite_differences() {
+        let activations = [
+            Activation::<f64, 0>::GELU,
+            Activation::GELUTanh,
+            Activation::SiLU,
+            Activation::ELU(0.5),
+            Activation::SELU,
+            Activation::Softplus,
+            Activation::Softsign,
+            Activation::Mish,
+            Activation::HardTanh,
+            Activation::HardSigmoid,
+        ];
+        // avoid the kinks of the piecewise functions
+        for x in [-2., -0.5, 0.7, 3.5] {
+            for activation in activations.iter() {
+                let check = check_gradient(|[x]| activation.compute(&array![[x]])[[0, 0]], [x]);
+                assert!(check.passes(1e-7), "{:?}", check);
+            }
+        }
     }
 }
@@ -0,0 +1,14 @@
+//! Replacement for the (removed) unstable `array_zip` feature
+
+/// Zip two arrays of equal length into an array of pairs
+pub(crate) trait ArrayZip<T, const N: usize> {
+    fn zip<U>(self, other: [U; N]) -> [(T, U); N];
+}
+
+impl<T, const N: usize> ArrayZip<T, N> for [T; N] {
+    #[inline]
+    fn zip<U>(self, other: [U; N]) -> [(T, U); N] {
+        let mut other = IntoIterator::into_iter(other);
+        self.map(|a| (a, other.next().unwrap()))
+    }
+}
@@ -1,3 +1,7 @@
+// Dual arithmetic is built from the component operations of the underlying float
+#![allow(clippy::suspicious_arithmetic_impl, clippy::suspicious_op_assign_impl)]
+
+use crate::autograd::ArrayZip;
 use num_traits::float::FloatCore;
 use num_traits::*;
 use std::cmp::Ordering;
@@ -22,10 +26,10 @@ impl<F: Num + PartialOrd + Copy, const N: usize> From<F> for Dual<F, N> {
     }
 }
 
-impl<'a, F: Num + PartialOrd + Copy, const N: usize> From<&'a F> for Dual<F, N> {
+impl<F: Num + PartialOrd + Copy, const N: usize> From<&F> for Dual<F, N> {
     #[inline]
     fn from(x: &F) -> Self {
-        From::from(x.clone())
+        From::from(*x)
     }
 }
 
@@ -81,15 +85,15 @@ macro_rules! forward_all_binop {
 impl<F: Num + Copy, const N: usize> Dual<F, N> {
     /// Create a new dual number, providing both its real part and the derivatives
     pub fn new(val: F, e: [F; N]) -> Self {
-        Dual { val: val, e: e }
+        Dual { val, e }
     }
 
     /// Create a constant dual number, meaning it has a derivative
     /// of zero
     pub fn constant(val: F) -> Self {
         Self {
-            val: val,
-            e: [F::zero().clone(); N],
+            val,
+            e: [F::zero(); N],
         }
     }
 
@@ -98,7 +102,7 @@ impl<F: Num + Copy, const N: usize> Dual<F, N> {
     pub fn variable(val: F, index: usize) -> Self {
         let mut e = [F::zero(); N];
         e[index] = F::one();
-        Self { val: val, e: e }
+        Self { val, e }
     }
 }
 
@@ -106,8 +110,8 @@ impl<F: Num + PartialOrd + Copy + Neg<Output = F>, const N: usize> Dual<F, N> {
     /// Invert the gradient
     pub fn conj(&self) -> Self {
         Self {
-            val: self.val.clone(),
-            e: self.e.clone().map(|a| a.neg()),
+            val: self.val,
+            e: self.e.map(|a| a.neg()),
         }
     }
 }
@@ -155,11 +159,11 @@ impl<F: Num + PartialOrd + Copy, const N: usize> Add for Dual<F, N> {
 
     #[inline]
     fn add(self, other: Self) -> Self::Output {
-        let zipped = self.e.clone().zip(other.e);
-        let e = zipped.clone().map(|(a, b)| a + b);
+        let zipped = self.e.zip(other.e);
+        let e = zipped.map(|(a, b)| a + b);
         Self {
             val: self.val + other.val,
-            e: e,
+            e,
         }
     }
 }
@@ -170,11 +174,11 @@ impl<F: Num + PartialOrd + Copy, const N: usize> Sub for Dual<F, N> {
 
     #[inline]
     fn sub(self, other: Self) -> Self::Output {
-        let zipped = self.e.clone().zip(other.e);
-        let e = zipped.clone().map(|(a, b)| a - b);
+        let zipped = self.e.zip(other.e);
+        let e = zipped.map(|(a, b)| a - b);
         Self {
             val: self.val - other.val,
-            e: e,
+            e,
         }
     }
 }
@@ -185,8 +189,8 @@ impl<F: Num + PartialOrd + Copy, const N: usize> Mul for Dual<F, N> {
 
     #[inline]
     fn mul(self, other: Self) -> Self::Output {
-        let e1 = self.e.clone().map(|e| other.val.clone() * e);
-        let e2 = other.e.map(|e| self.val.clone() * e);
+        let e1 = self.e.map(|e| other.val * e);
+        let e2 = other.e.map(|e| self.val * e);
         let sum = e1.zip(e2).map(|(a, b)| a + b);
 
         Self {
@@ -202,13 +206,13 @@ impl<F: Num + PartialOrd + Copy, const N: usize> Div for Dual<F, N> {
 
     #[inline]
     fn div(self, other: Self) -> Self::Output {
-        let e1 = self.e.clone().map(|e| other.val.clone() * e);
-        let e2 = other.e.clone().map(|e| self.val.clone() * e);
+        let e1 = self.e.map(|e| other.val * e);
+        let e2 = other.e.map(|e| self.val * e);
         Self {
-            val: self.val / other.clone().val,
+            val: self.val / other.val,
             e: e1
                 .zip(e2)
-                .map(|(a, b)| (a - b) / (other.val.clone() * other.val.clone())),
+                .map(|(a, b)| (a - b) / (other.val * other.val)),
         }
     }
 }
@@ -289,8 +293,7 @@ macro_rules! real_arithmetic {
 
                 #[inline]
                 fn sub(self, other: Dual<$real, N>) -> Self::Output  {
-                    unimplemented!();
-                    // Self::Output::new(self - other.val, other.e.map(|x| x.neg()))
+                    Self::Output::new(self - other.val, other.e.map(|x| x.neg()))
                 }
             }
 
@@ -308,7 +311,8 @@ macro_rules! real_arithmetic {
 
                 #[inline]
                 fn div(self, other: Dual<$real, N>) -> Self::Output {
-                    Self::Output::new(self / other.val, [$real::zero(); N])
+                    let factor = -self / (other.val * other.val);
+                    Self::Output::new(self / other.val, other.e.map(|x| factor * x))
                 }
             }
 
@@ -347,7 +351,7 @@ impl<F: Num + PartialOrd + Copy, const N: usize> Mul<F> for Dual<F, N> {
 
     #[inline]
     fn mul(self, other: F) -> Self::Output {
-        Self::Output::new(self.val * other.clone(), self.e.map(|x| x * other.clone()))
+        Self::Output::new(self.val * other, self.e.map(|x| x * other))
     }
 }
 
@@ -356,7 +360,7 @@ impl<F: Num + PartialOrd + Copy, const N: usize> Div<F> for Dual<F, N> {
 
     #[inline]
     fn div(self, other: F) -> Self::Output {
-        Self::Output::new(self.val / other.clone(), self.e.map(|x| x / other.clone()))
+        Self::Output::new(self.val / other, self.e.map(|x| x / other))
     }
 }
 
@@ -365,37 +369,37 @@ impl<F: Num + PartialOrd + Copy, const N: usize> Rem<F> for Dual<F, N> {
 
     #[inline]
     fn rem(self, other: F) -> Self::Output {
-        Self::Output::new(self.val % other.clone(), self.e.map(|x| x / other.clone()))
+        Self::Output::new(self.val % other, self.e)
     }
 }
 
 real_arithmetic!(f32, f64);
 
 mod opassign {
-    use crate::autograd::Dual;
+    use crate::autograd::{ArrayZip, Dual};
     use core::ops::{AddAssign, DivAssign, MulAssign, RemAssign, SubAssign};
     use num_traits::*;
 
     impl<F: NumAssign + Copy, const N: usize> AddAssign for Dual<F, N> {
         fn add_assign(&mut self, other: Self) {
-            let zipped = self.e.clone().zip(other.e);
+            let zipped = self.e.zip(other.e);
             self.val += other.val;
-            self.e = zipped.clone().map(|(a, b)| a + b);
+            self.e = zipped.map(|(a, b)| a + b);
         }
     }
 
     impl<F: NumAssign + Copy, const N: usize> SubAssign for Dual<F, N> {
         fn sub_assign(&mut self, other: Self) {
-            let zipped = self.e.clone().zip(other.e);
+            let zipped = self.e.zip(other.e);
             self.val -= other.val;
-            self.e = zipped.clone().map(|(a, b)| a - b);
+            self.e = zipped.map(|(a, b)| a - b);
         }
     }
 
     impl<F: NumAssign + Copy, const N: usize> MulAssign for Dual<F, N> {
         fn mul_assign(&mut self, other: Self) {
-            let e1 = self.e.clone().map(|e| other.val.clone() * e);
-            let e2 = other.e.map(|e| self.val.clone() * e);
+            let e1 = self.e.map(|e| other.val * e);
+            let e2 = other.e.map(|e| self.val * e);
             self.val *= other.val;
             self.e = e1.zip(e2).map(|(a, b)| a + b);
         }
@@ -403,24 +407,21 @@ mod opassign {
 
     impl<F: NumAssign + Copy, const N: usize> DivAssign for Dual<F, N> {
         fn div_assign(&mut self, other: Self) {
-            let e1 = self.e.clone().map(|e| other.val.clone() * e);
-            let e2 = other.e.clone().map(|e| self.val.clone() * e);
+            let e1 = self.e.map(|e| other.val * e);
+            let e2 = other.e.map(|e| self.val * e);
             self.e = e1
                 .zip(e2)
-                .map(|(a, b)| (a - b) / (other.val.clone() * other.val.clone()));
+                .map(|(a, b)| (a - b) / (other.val * other.val));
             self.val /= other.val;
         }
     }
 
     impl<F: NumAssign + Copy, const N: usize> RemAssign for Dual<F, N> {
         fn rem_assign(&mut self, other: Self) {
-            let e1 = self.e.clone().map(|e| other.val.clone() * e);
-            let e2 = other.e.clone().map(|e| self.val.clone() * e);
-            let res = e1
-                .zip(e2)
-                .map(|(a, b)| (a - b) / (other.val.clone() * other.val.clone()));
-            self.val %= other.val;
-            self.e = res;
+            let val = self.val % other.val;
+            let quotient = (self.val - val) / other.val;
+            self.e = self.e.zip(other.e).map(|(a, b)| a - b * quotient);
+            self.val = val;
         }
     }
 
@@ -438,21 +439,20 @@ mod opassign {
 
     impl<F: NumAssign + Copy, const N: usize> MulAssign<F> for Dual<F, N> {
         fn mul_assign(&mut self, other: F) {
-            self.e = self.e.clone().map(|x| x * other.clone());
+            self.e = self.e.map(|x| x * other);
             self.val *= other;
         }
     }
 
     impl<F: NumAssign + Copy, const N: usize> DivAssign<F> for Dual<F, N> {
         fn div_assign(&mut self, other: F) {
-            self.e = self.e.clone().map(|x| x / other.clone());
+            self.e = self.e.map(|x| x / other);
             self.val /= other;
         }
     }
 
     impl<F: NumAssign + Copy, const N: usize> RemAssign<F> for Dual<F, N> {
         fn rem_assign(&mut self, other: F) {
-            self.e = self.e.clone().map(|x| x / other.clone());
             self.val %= other;
         }
     }
@@ -488,19 +488,19 @@ impl<F: Num + PartialOrd + Copy + Neg<Output = F>, const N: usize> Neg for Dual<
     fn neg(self) -> Self::Output {
         Dual {
             val: self.val.neg(),
-            e: self.e,
+            e: self.e.map(|x| x.neg()),
         }
     }
 }
 
-impl<'a, F: Num + PartialOrd + Copy + Neg<Output = F>, const N: usize> Neg for &'a Dual<F, N> {
+impl<F: Num + PartialOrd + Copy + Neg<Output = F>, const N: usize> Neg for &Dual<F, N> {
     type Output = Dual<F, N>;
 
     #[inline]
     fn neg(self) -> Self::Output {
         Dual {
-            val: self.val.clone().neg(),
-            e: self.e.clone(),
+            val: self.val.neg(),
+            e: self.e.map(|x| x.neg()),
         }
     }
 }
@@ -548,10 +548,13 @@ impl<F: Num + PartialOrd + Copy, const N: usize> One for Dual<F, N> {
 
 impl<F: Num + PartialOrd + Copy, const N: usize> Rem for Dual<F, N> {
     type Output = Self;
+    // x % y = x - trunc(x / y) * y, whose quotient is locally constant
     fn rem(self, other: Self) -> Self::Output {
+        let val = self.val % other.val;
+        let quotient = (self.val - val) / other.val;
         Dual {
-            val: self.val % other.val,
-            e: self.e,
+            val,
+            e: self.e.zip(other.e).map(|(a, b)| a - b * quotient),
         }
     }
 }
@@ -588,7 +591,7 @@ where
     D: NotADual,
 {
     fn partial_cmp(&self, other: &D) -> Option<Ordering> {
-        self.val.partial_cmp(&other)
+        self.val.partial_cmp(other)
     }
 }
 
@@ -648,7 +651,7 @@ impl<F: Float, const N: usize> Float for Dual<F, N> {
     float_impl_self_passthrough!(floor, ceil, round, trunc);
 
     fn fract(self) -> Self {
-        Dual::new(self.val.fract(), [F::one(); N])
+        Dual::new(self.val.fract(), self.e)
     }
 
     fn abs(self) -> Self {
@@ -665,7 +668,7 @@ impl<F: Float, const N: usize> Float for Dual<F, N> {
             .e
             .zip(a.e)
             .zip(b.e)
-            .map(|((d, e), f)| d * a.val + self.val * e * f);
+            .map(|((d, e), f)| d * a.val + self.val * e + f);
         Dual::new(self.val.mul_add(a.val, b.val), e)
     }
 
@@ -675,16 +678,21 @@ impl<F: Float, const N: usize> Float for Dual<F, N> {
 
     fn powi(self, n: i32) -> Self {
         let exp = F::from(n).unwrap();
-        let e = self.e.map(|x| exp * x.powi(n - 1));
+        let e = self.e.map(|x| exp * self.val.powi(n - 1) * x);
         Dual::new(self.val.powi(n), e)
     }
 
     fn powf(self, n: Self) -> Self {
         let val = self.val.powf(n.val);
-        let e = self
-            .e
-            .zip(n.e)
-            .map(|(a, b)| n.val * self.val.powf(b - F::one()) * a + val * self.val.ln() * b);
+        let d_base = n.val * self.val.powf(n.val - F::one());
+        let e = self.e.zip(n.e).map(|(a, b)| {
+            // skip ln(x), which is undefined for negative bases, when the exponent is constant
+            if b.is_zero() {
+                d_base * a
+            } else {
+                d_base * a + val * self.val.ln() * b
+            }
+        });
         Dual::new(val, e)
     }
 
@@ -716,7 +724,7 @@ impl<F: Float, const N: usize> Float for Dual<F, N> {
     }
 
     fn log2(self) -> Self {
-        let e = self.e.map(|x| x / (self.val * F::from(10).unwrap().ln()));
+        let e = self.e.map(|x| x / (self.val * F::from(2).unwrap().ln()));
         Dual::new(self.val.log2(), e)
     }
 
@@ -762,7 +770,7 @@ impl<F: Float, const N: usize> Float for Dual<F, N> {
     fn hypot(self, other: Self) -> Self {
         let real = self.val.hypot(other.val);
         let zipped = self.e.zip(other.e);
-        let e = zipped.map(|(a, b)| (self.val * b + other.val * a) / real);
+        let e = zipped.map(|(a, b)| (self.val * a + other.val * b) / real);
         Dual::new(real, e)
     }
 
@@ -800,7 +808,7 @@ impl<F: Float, const N: usize> Float for Dual<F, N> {
     fn atan2(self, other: Self) -> Self {
         let zipped = self.e.zip(other.e);
         let e = zipped
-            .map(|(a, b)| (self.val * b - other.val * a) / (self.val.powi(2) + other.val.powi(2)));
+            .map(|(a, b)| (other.val * a - self.val * b) / (self.val.powi(2) + other.val.powi(2)));
         Dual::new(self.val.atan2(other.val), e)
     }
 
@@ -824,7 +832,7 @@ impl<F: Float, const N: usize> Float for Dual<F, N> {
     }
 
     fn cosh(self) -> Self {
-        let e = self.e.map(|x| x * x.sinh());
+        let e = self.e.map(|x| x * self.val.sinh());
         Dual::new(self.val.cosh(), e)
     }
 
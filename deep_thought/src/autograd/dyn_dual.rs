@@ -0,0 +1,847 @@
+//! Dual numbers whose number of tangents is only known at runtime
+//!
+//! A [`Dual<F, N>`](crate::autograd::Dual) stores its tangents inline, which requires the number of
+//! variables to be a compile-time constant. [`DynDual`] instead stores its tangents inside a shared
+//! [`TangentBuffer`] and only keeps a handle to them, which keeps the number itself [`Copy`] (as
+//! required by [`Float`]) while the number of variables can be chosen at runtime.
+//!
+//! The buffer decides how tangents are laid out, [`Tangents`] stores them densely while
+//! [`SparseTangents`](crate::autograd::SparseTangents) only stores the nonzero entries.
+//! A [`Tape`](crate::autograd::Tape) does not store tangents at all but records the operations
+//! instead, which turns [`DynDual`] into a reverse-mode [`Var`](crate::autograd::Var).
+//!
+//! Since [`DynDual`] is [`Copy`], the buffer can not know on its own which rows are still in use.
+//! Callers release the rows of values they are done with through [`TangentBuffer::retain`], e.g.
+//! [`NeuralNetwork::forward_dyn`](crate::neural_network::NeuralNetwork::forward_dyn) only keeps the
+//! output of every layer, and later operations reuse the released storage.
+
+use num_traits::*;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::*;
+
+/// Storage for the tangents of [`DynDual`] numbers.
+///
+/// Every operation on a non-constant [`DynDual`] stores a new tangent row within the buffer, which
+/// stays allocated until it is released by [`TangentBuffer::retain`].
+pub trait TangentBuffer<F> {
+    /// Handle to a single tangent row within the buffer
+    type Row: Copy;
+
+    /// Number of variables the derivatives are tracked for
+    fn num_variables(&self) -> usize;
+
+    /// Store a new row which has a derivative of one with respect to the given variable
+    fn seed(&self, index: usize) -> Self::Row;
+
+    /// Store a new row containing `row * factor`
+    fn scale(&self, row: Self::Row, factor: F) -> Self::Row;
+
+    /// Store a new row containing `a * factor_a + b * factor_b`
+    fn combine(&self, a: Self::Row, b: Self::Row, factor_a: F, factor_b: F) -> Self::Row;
+
+    /// Get the derivative with respect to the variable with the given index
+    fn derivative(&self, row: Self::Row, index: usize) -> F;
+
+    /// Get the derivatives with respect to every variable
+    fn gradient(&self, row: Self::Row) -> Vec<F>;
+
+    /// Whether the derivatives with respect to every variable are zero
+    fn is_constant(&self, row: Self::Row) -> bool
+    where
+        F: Zero,
+    {
+        self.gradient(row).iter().all(|x| x.is_zero())
+    }
+
+    /// Release the storage of every row except `live`, so that later operations can reuse it.
+    ///
+    /// Buffers whose derivatives depend on every recorded operation, like a
+    /// [`Tape`](crate::autograd::Tape), keep all of their rows.
+    fn retain(&self, _live: &[Self::Row]) {}
+
+    /// Create a variable dual number, meaning it has a derivative of one with
+    /// respect to itself. Every variable must be assigned a unique index
+    fn variable(&self, val: F, index: usize) -> DynDual<'_, F, Self>
+    where
+        Self: Sized,
+    {
+        assert!(
+            index < self.num_variables(),
+            "variable index {} out of range for {} variables",
+            index,
+            self.num_variables()
+        );
+        DynDual {
+            val,
+            tangent: Some(Tangent {
+                buffer: self,
+                row: self.seed(index),
+            }),
+        }
+    }
+}
+
+/// Handle to a row of [`Tangents`] or [`SparseTangents`](crate::autograd::SparseTangents).
+///
+/// Using a handle after its row was released by [`TangentBuffer::retain`] panics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TangentRow {
+    slot: usize,
+    generation: u32,
+}
+
+/// Tangent rows whose storage is reused once they are released
+pub(crate) struct Rows<R> {
+    rows: Vec<R>,
+    /// incremented whenever a slot is released, so handles to released rows can be detected
+    generations: Vec<u32>,
+    /// whether each slot holds a row which has not been released
+    live: Vec<bool>,
+    /// released slots waiting to be reused
+    free: Vec<usize>,
+}
+
+impl<R: Default> Rows<R> {
+    pub(crate) fn new() -> Self {
+        Rows {
+            rows: vec![],
+            generations: vec![],
+            live: vec![],
+            free: vec![],
+        }
+    }
+
+    /// Number of slots, including released ones
+    pub(crate) fn capacity(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub(crate) fn get(&self, row: TangentRow) -> &R {
+        assert_eq!(
+            self.generations[row.slot], row.generation,
+            "tangent row was used after being released"
+        );
+        &self.rows[row.slot]
+    }
+
+    /// Iterate over the rows which have not been released
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &R> {
+        self.rows
+            .iter()
+            .zip(&self.live)
+            .filter_map(|(row, &live)| live.then_some(row))
+    }
+
+    /// Store a new row. `fill` receives the existing rows and the storage of the new row, which
+    /// might still contain the entries of a released row.
+    pub(crate) fn insert(&mut self, fill: impl FnOnce(&Self, &mut R)) -> TangentRow {
+        let slot = self.free.pop().unwrap_or_else(|| {
+            self.rows.push(R::default());
+            self.generations.push(0);
+            self.live.push(false);
+            self.rows.len() - 1
+        });
+        let mut row = std::mem::take(&mut self.rows[slot]);
+        fill(self, &mut row);
+        self.rows[slot] = row;
+        self.live[slot] = true;
+        TangentRow {
+            slot,
+            generation: self.generations[slot],
+        }
+    }
+
+    /// Release every row except `live`
+    pub(crate) fn retain(&mut self, live: &[TangentRow]) {
+        let mut keep = vec![false; self.rows.len()];
+        for &row in live {
+            self.get(row);
+            keep[row.slot] = true;
+        }
+        for (slot, keep) in keep.into_iter().enumerate() {
+            if self.live[slot] && !keep {
+                self.live[slot] = false;
+                self.generations[slot] = self.generations[slot].wrapping_add(1);
+                self.free.push(slot);
+            }
+        }
+    }
+}
+
+/// Dense [`TangentBuffer`], every row stores the derivative with respect to every variable
+pub struct Tangents<F> {
+    num_variables: usize,
+    rows: RefCell<Rows<Vec<F>>>,
+}
+
+impl<F: Num + Copy> Tangents<F> {
+    /// Create an empty tangent buffer tracking derivatives with respect to `num_variables` variables
+    pub fn new(num_variables: usize) -> Self {
+        Tangents {
+            num_variables,
+            rows: RefCell::new(Rows::new()),
+        }
+    }
+
+    /// Number of rows the buffer has allocated storage for, including released rows which are
+    /// waiting to be reused
+    pub fn num_rows(&self) -> usize {
+        self.rows.borrow().capacity()
+    }
+
+    /// Store a new tangent row, `fill` appends its entries given the existing rows
+    fn push(&self, fill: impl FnOnce(&Rows<Vec<F>>, &mut Vec<F>)) -> TangentRow {
+        self.rows.borrow_mut().insert(|rows, row| {
+            row.clear();
+            fill(rows, row);
+            debug_assert_eq!(row.len(), self.num_variables);
+        })
+    }
+}
+
+impl<F: Num + Copy> TangentBuffer<F> for Tangents<F> {
+    type Row = TangentRow;
+
+    fn num_variables(&self) -> usize {
+        self.num_variables
+    }
+
+    fn seed(&self, index: usize) -> TangentRow {
+        self.push(|_, row| {
+            let one_hot = |i| if i == index { F::one() } else { F::zero() };
+            row.extend((0..self.num_variables).map(one_hot))
+        })
+    }
+
+    fn scale(&self, row: TangentRow, factor: F) -> TangentRow {
+        self.push(|rows, out| out.extend(rows.get(row).iter().map(|&x| x * factor)))
+    }
+
+    fn combine(&self, a: TangentRow, b: TangentRow, factor_a: F, factor_b: F) -> TangentRow {
+        self.push(|rows, out| {
+            let (a, b) = (rows.get(a), rows.get(b));
+            out.extend(a.iter().zip(b).map(|(&x, &y)| x * factor_a + y * factor_b))
+        })
+    }
+
+    fn derivative(&self, row: TangentRow, index: usize) -> F {
+        self.rows.borrow().get(row)[index]
+    }
+
+    fn gradient(&self, row: TangentRow) -> Vec<F> {
+        self.rows.borrow().get(row).clone()
+    }
+
+    fn is_constant(&self, row: TangentRow) -> bool {
+        self.rows.borrow().get(row).iter().all(|x| x.is_zero())
+    }
+
+    fn retain(&self, live: &[TangentRow]) {
+        self.rows.borrow_mut().retain(live)
+    }
+}
+
+/// Handle to a tangent row within a [`TangentBuffer`]
+struct Tangent<'t, F, T: TangentBuffer<F>> {
+    buffer: &'t T,
+    row: T::Row,
+}
+
+impl<'t, F, T: TangentBuffer<F>> Clone for Tangent<'t, F, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'t, F, T: TangentBuffer<F>> Copy for Tangent<'t, F, T> {}
+
+/// A dual number with a runtime number of tangents, see the [module level documentation](self)
+///
+/// Constants are not associated with any [`TangentBuffer`], their derivatives are implicitly zero.
+pub struct DynDual<'t, F, T: TangentBuffer<F> = Tangents<F>> {
+    /// real value
+    pub val: F,
+    tangent: Option<Tangent<'t, F, T>>,
+}
+
+impl<'t, F: Copy, T: TangentBuffer<F>> Clone for DynDual<'t, F, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'t, F: Copy, T: TangentBuffer<F>> Copy for DynDual<'t, F, T> {}
+
+impl<'t, F: Num + Copy, T: TangentBuffer<F>> DynDual<'t, F, T> {
+    /// Create a constant dual number, meaning it has a derivative
+    /// of zero
+    pub fn constant(val: F) -> Self {
+        DynDual { val, tangent: None }
+    }
+
+    /// Get the derivative with respect to the variable with the given index
+    pub fn derivative(&self, index: usize) -> F {
+        match self.tangent {
+            Some(Tangent { buffer, row }) => buffer.derivative(row, index),
+            None => F::zero(),
+        }
+    }
+
+    /// Get the derivatives with respect to every variable.
+    /// Constants which were never combined with a variable have an empty gradient.
+    pub fn gradient(&self) -> Vec<F> {
+        match self.tangent {
+            Some(Tangent { buffer, row }) => buffer.gradient(row),
+            None => vec![],
+        }
+    }
+
+    /// Handle to the value's tangent row within its [`TangentBuffer`], `None` for constants.
+    /// Pass it to [`TangentBuffer::retain`] to keep the value usable.
+    pub fn row(&self) -> Option<T::Row> {
+        self.tangent.map(|tangent| tangent.row)
+    }
+
+    /// Whether all derivatives are zero
+    fn has_zero_derivatives(&self) -> bool {
+        match self.tangent {
+            Some(Tangent { buffer, row }) => buffer.is_constant(row),
+            None => true,
+        }
+    }
+
+    /// Apply a function to the real part, given its derivative `deriv` at `self.val`
+    fn chain(self, val: F, deriv: F) -> Self {
+        let tangent = self.tangent.map(|Tangent { buffer, row }| Tangent {
+            buffer,
+            row: buffer.scale(row, deriv),
+        });
+        DynDual { val, tangent }
+    }
+
+    /// Apply a binary function to the real parts, given its partial derivatives
+    /// `d_self` and `d_other` at `(self.val, other.val)`
+    fn chain2(self, other: Self, val: F, d_self: F, d_other: F) -> Self {
+        match (self.tangent, other.tangent) {
+            (None, None) => DynDual::constant(val),
+            (Some(_), None) => self.chain(val, d_self),
+            (None, Some(_)) => other.chain(val, d_other),
+            (Some(a), Some(b)) => {
+                debug_assert!(std::ptr::eq(a.buffer, b.buffer));
+                let row = a.buffer.combine(a.row, b.row, d_self, d_other);
+                DynDual {
+                    val,
+                    tangent: Some(Tangent {
+                        buffer: a.buffer,
+                        row,
+                    }),
+                }
+            }
+        }
+    }
+}
+
+impl<'t, F: Num + Copy + fmt::Debug, T: TangentBuffer<F>> fmt::Debug for DynDual<'t, F, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynDual")
+            .field("val", &self.val)
+            .field("e", &self.gradient())
+            .finish()
+    }
+}
+
+impl<'t, F: Num + Copy, T: TangentBuffer<F>> From<F> for DynDual<'t, F, T> {
+    #[inline]
+    fn from(x: F) -> Self {
+        Self::constant(x)
+    }
+}
+
+impl<'t, F: Num + Copy, T: TangentBuffer<F>> Add for DynDual<'t, F, T> {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, other: Self) -> Self::Output {
+        self.chain2(other, self.val + other.val, F::one(), F::one())
+    }
+}
+
+impl<'t, F: Num + Copy + Neg<Output = F>, T: TangentBuffer<F>> Sub for DynDual<'t, F, T> {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, other: Self) -> Self::Output {
+        self.chain2(other, self.val - other.val, F::one(), F::one().neg())
+    }
+}
+
+impl<'t, F: Num + Copy, T: TangentBuffer<F>> Mul for DynDual<'t, F, T> {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, other: Self) -> Self::Output {
+        self.chain2(other, self.val * other.val, other.val, self.val)
+    }
+}
+
+impl<'t, F: Num + Copy + Neg<Output = F>, T: TangentBuffer<F>> Div for DynDual<'t, F, T> {
+    type Output = Self;
+
+    #[inline]
+    fn div(self, other: Self) -> Self::Output {
+        let d_other = (self.val / (other.val * other.val)).neg();
+        self.chain2(other, self.val / other.val, F::one() / other.val, d_other)
+    }
+}
+
+impl<'t, F: Num + Copy + Neg<Output = F>, T: TangentBuffer<F>> Rem for DynDual<'t, F, T> {
+    type Output = Self;
+
+    // x % y = x - trunc(x / y) * y, whose quotient is locally constant
+    #[inline]
+    fn rem(self, other: Self) -> Self::Output {
+        let val = self.val % other.val;
+        let quotient = (self.val - val) / other.val;
+        self.chain2(other, val, F::one(), -quotient)
+    }
+}
+
+impl<'t, F: Num + Copy + Neg<Output = F>, T: TangentBuffer<F>> Neg for DynDual<'t, F, T> {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self::Output {
+        self.chain(self.val.neg(), F::one().neg())
+    }
+}
+
+macro_rules! real_arithmetic {
+    ($($imp:ident::$method:ident),*) => {
+        $(
+            impl<'t, F: Num + Copy + Neg<Output = F>, T: TangentBuffer<F>> $imp<F> for DynDual<'t, F, T> {
+                type Output = Self;
+
+                #[inline]
+                fn $method(self, other: F) -> Self::Output {
+                    self.$method(DynDual::constant(other))
+                }
+            }
+        )*
+    };
+}
+
+real_arithmetic!(Add::add, Sub::sub, Mul::mul, Div::div, Rem::rem);
+
+macro_rules! op_assign {
+    ($($imp:ident::$method:ident => $op:ident),*) => {
+        $(
+            impl<'t, F: Num + Copy + Neg<Output = F>, T: TangentBuffer<F>> $imp for DynDual<'t, F, T> {
+                #[inline]
+                fn $method(&mut self, other: Self) {
+                    *self = (*self).$op(other);
+                }
+            }
+
+            impl<'t, F: Num + Copy + Neg<Output = F>, T: TangentBuffer<F>> $imp<F> for DynDual<'t, F, T> {
+                #[inline]
+                fn $method(&mut self, other: F) {
+                    *self = (*self).$op(other);
+                }
+            }
+        )*
+    };
+}
+
+op_assign!(
+    AddAssign::add_assign => add,
+    SubAssign::sub_assign => sub,
+    MulAssign::mul_assign => mul,
+    DivAssign::div_assign => div,
+    RemAssign::rem_assign => rem
+);
+
+impl<'t, F: Num + Copy, T: TangentBuffer<F>> Zero for DynDual<'t, F, T> {
+    fn zero() -> Self {
+        DynDual::constant(F::zero())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.val.is_zero() && self.has_zero_derivatives()
+    }
+}
+
+impl<'t, F: Num + Copy, T: TangentBuffer<F>> One for DynDual<'t, F, T> {
+    fn one() -> Self {
+        DynDual::constant(F::one())
+    }
+
+    fn is_one(&self) -> bool {
+        self.val.is_one() && self.has_zero_derivatives()
+    }
+}
+
+impl<'t, F: PartialEq, T: TangentBuffer<F>> PartialEq for DynDual<'t, F, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.val == other.val
+    }
+}
+
+impl<'t, F: PartialOrd, T: TangentBuffer<F>> PartialOrd for DynDual<'t, F, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.val.partial_cmp(&other.val)
+    }
+}
+
+impl<'t, F: Num + Copy + Neg<Output = F>, T: TangentBuffer<F>> Num for DynDual<'t, F, T> {
+    type FromStrRadixErr = <F as Num>::FromStrRadixErr;
+    fn from_str_radix(string: &str, radix: u32) -> Result<Self, <Self as Num>::FromStrRadixErr> {
+        Ok(DynDual::constant(F::from_str_radix(string, radix)?))
+    }
+}
+
+macro_rules! impl_to_primitive {
+    ($($ty:ty, $to:ident);*) => {
+        $(
+            #[inline]
+            fn $to(&self) -> Option<$ty> {
+                self.val.$to()
+            }
+        )*
+    };
+}
+
+// Clips the non-real part
+impl<'t, F: ToPrimitive, T: TangentBuffer<F>> ToPrimitive for DynDual<'t, F, T> {
+    impl_to_primitive!(
+        usize, to_usize; isize, to_isize; u8, to_u8; u16, to_u16; u32, to_u32; u64, to_u64;
+        i8, to_i8; i16, to_i16; i32, to_i32; i64, to_i64; u128, to_u128; i128, to_i128;
+        f32, to_f32; f64, to_f64
+    );
+}
+
+macro_rules! impl_from_primitive {
+    ($($ty:ty, $from:ident);*) => {
+        $(
+            #[inline]
+            fn $from(n: $ty) -> Option<Self> {
+                Some(DynDual::constant(F::$from(n)?))
+            }
+        )*
+    };
+}
+
+impl<'t, F: FromPrimitive + Num + Copy, T: TangentBuffer<F>> FromPrimitive for DynDual<'t, F, T> {
+    impl_from_primitive!(
+        usize, from_usize; isize, from_isize; u8, from_u8; u16, from_u16; u32, from_u32;
+        u64, from_u64; i8, from_i8; i16, from_i16; i32, from_i32; i64, from_i64;
+        u128, from_u128; i128, from_i128; f32, from_f32; f64, from_f64
+    );
+}
+
+impl<'t, F: NumCast + Num + Copy, T: TangentBuffer<F>> NumCast for DynDual<'t, F, T> {
+    fn from<U: ToPrimitive>(n: U) -> Option<Self> {
+        Some(DynDual::constant(<F as NumCast>::from(n)?))
+    }
+}
+
+macro_rules! float_impl_basic {
+    ($($name:ident),*) => {
+        $(fn $name() -> Self {
+            DynDual::constant(F::$name())
+        })*
+    }
+}
+
+macro_rules! float_impl_passthrough {
+    ($result:ty, $($name:ident),*) => {
+        $(fn $name(self) -> $result {
+            self.val.$name()
+        })*
+    }
+}
+
+macro_rules! float_impl_self_passthrough {
+    ($($name:ident),*) => {
+        $(fn $name(self) -> Self {
+            DynDual::constant(self.val.$name())
+        })*
+    }
+}
+
+impl<'t, F: Float, T: TangentBuffer<F>> Float for DynDual<'t, F, T> {
+    float_impl_basic!(
+        nan,
+        infinity,
+        neg_infinity,
+        neg_zero,
+        min_value,
+        max_value,
+        min_positive_value
+    );
+    float_impl_passthrough!(
+        bool,
+        is_nan,
+        is_infinite,
+        is_finite,
+        is_normal,
+        is_sign_positive,
+        is_sign_negative
+    );
+    float_impl_passthrough!((u64, i16, i8), integer_decode);
+    float_impl_passthrough!(::std::num::FpCategory, classify);
+    float_impl_self_passthrough!(floor, ceil, round, trunc, signum);
+
+    fn fract(self) -> Self {
+        self.chain(self.val.fract(), F::one())
+    }
+
+    fn abs(self) -> Self {
+        self.chain(self.val.abs(), self.val.signum())
+    }
+
+    fn mul_add(self, a: Self, b: Self) -> Self {
+        let product = self * a + b;
+        DynDual {
+            val: self.val.mul_add(a.val, b.val),
+            ..product
+        }
+    }
+
+    fn recip(self) -> Self {
+        self.chain(self.val.recip(), -self.val.powi(2).recip())
+    }
+
+    fn powi(self, n: i32) -> Self {
+        let deriv = F::from(n).unwrap() * self.val.powi(n - 1);
+        self.chain(self.val.powi(n), deriv)
+    }
+
+    fn powf(self, n: Self) -> Self {
+        let val = self.val.powf(n.val);
+        let d_self = n.val * self.val.powf(n.val - F::one());
+        let d_n = if n.tangent.is_some() {
+            val * self.val.ln()
+        } else {
+            F::zero()
+        };
+        self.chain2(n, val, d_self, d_n)
+    }
+
+    fn sqrt(self) -> Self {
+        let val = self.val.sqrt();
+        self.chain(val, (F::from(2).unwrap() * val).recip())
+    }
+
+    fn exp(self) -> Self {
+        let val = self.val.exp();
+        self.chain(val, val)
+    }
+
+    fn exp2(self) -> Self {
+        let val = self.val.exp2();
+        self.chain(val, val * F::from(2).unwrap().ln())
+    }
+
+    fn ln(self) -> Self {
+        self.chain(self.val.ln(), self.val.recip())
+    }
+
+    fn log(self, base: Self) -> Self {
+        self.ln() / base.ln()
+    }
+
+    fn log2(self) -> Self {
+        let deriv = (self.val * F::from(2).unwrap().ln()).recip();
+        self.chain(self.val.log2(), deriv)
+    }
+
+    fn log10(self) -> Self {
+        let deriv = (self.val * F::from(10).unwrap().ln()).recip();
+        self.chain(self.val.log10(), deriv)
+    }
+
+    fn max(self, other: Self) -> Self {
+        if self.val >= other.val {
+            self
+        } else {
+            other
+        }
+    }
+
+    fn min(self, other: Self) -> Self {
+        if self.val <= other.val {
+            self
+        } else {
+            other
+        }
+    }
+
+    fn abs_sub(self, other: Self) -> Self {
+        if self.val > other.val {
+            self - other
+        } else {
+            DynDual::zero()
+        }
+    }
+
+    fn cbrt(self) -> Self {
+        let val = self.val.cbrt();
+        self.chain(val, (F::from(3).unwrap() * val.powi(2)).recip())
+    }
+
+    fn hypot(self, other: Self) -> Self {
+        let val = self.val.hypot(other.val);
+        self.chain2(other, val, self.val / val, other.val / val)
+    }
+
+    fn sin(self) -> Self {
+        self.chain(self.val.sin(), self.val.cos())
+    }
+
+    fn cos(self) -> Self {
+        self.chain(self.val.cos(), -self.val.sin())
+    }
+
+    fn tan(self) -> Self {
+        self.chain(self.val.tan(), self.val.cos().powi(2).recip())
+    }
+
+    fn asin(self) -> Self {
+        let deriv = (F::one() - self.val.powi(2)).sqrt().recip();
+        self.chain(self.val.asin(), deriv)
+    }
+
+    fn acos(self) -> Self {
+        let deriv = -(F::one() - self.val.powi(2)).sqrt().recip();
+        self.chain(self.val.acos(), deriv)
+    }
+
+    fn atan(self) -> Self {
+        self.chain(self.val.atan(), (F::one() + self.val.powi(2)).recip())
+    }
+
+    fn atan2(self, other: Self) -> Self {
+        let denom = self.val.powi(2) + other.val.powi(2);
+        self.chain2(
+            other,
+            self.val.atan2(other.val),
+            other.val / denom,
+            -self.val / denom,
+        )
+    }
+
+    fn sin_cos(self) -> (Self, Self) {
+        (self.sin(), self.cos())
+    }
+
+    fn exp_m1(self) -> Self {
+        self.chain(self.val.exp_m1(), self.val.exp())
+    }
+
+    fn ln_1p(self) -> Self {
+        self.chain(self.val.ln_1p(), (F::one() + self.val).recip())
+    }
+
+    fn sinh(self) -> Self {
+        self.chain(self.val.sinh(), self.val.cosh())
+    }
+
+    fn cosh(self) -> Self {
+        self.chain(self.val.cosh(), self.val.sinh())
+    }
+
+    fn tanh(self) -> Self {
+        let val = self.val.tanh();
+        self.chain(val, F::one() - val.powi(2))
+    }
+
+    fn asinh(self) -> Self {
+        let deriv = (self.val.powi(2) + F::one()).sqrt().recip();
+        self.chain(self.val.asinh(), deriv)
+    }
+
+    fn acosh(self) -> Self {
+        let deriv = (self.val.powi(2) - F::one()).sqrt().recip();
+        self.chain(self.val.acosh(), deriv)
+    }
+
+    fn atanh(self) -> Self {
+        let deriv = (F::one() - self.val.powi(2)).recip();
+        self.chain(self.val.atanh(), deriv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_tracks_runtime_number_of_variables() {
+        let tangents = Tangents::new(3);
+        let x = tangents.variable(2., 0);
+        let y = tangents.variable(3., 1);
+        let z = tangents.variable(0.5, 2);
+
+        // f(x, y, z) = x * y - x / z + 4
+        let f = x * y - x / z + 4.;
+        assert_eq!(f.val, 6.);
+        assert_eq!(f.gradient(), vec![1., 2., 8.]);
+    }
+
+    #[test]
+    fn float_functions_apply_chain_rule() {
+        let tangents = Tangents::new(2);
+        let x = tangents.variable(0.3, 0);
+        let y = tangents.variable(1.7, 1);
+
+        let f = x.sin() * y.exp() + x.tanh().powi(2);
+        let expected_dx = 0.3f64.cos() * 1.7f64.exp()
+            + 2. * 0.3f64.tanh() * (1. - 0.3f64.tanh().powi(2));
+        let expected_dy = 0.3f64.sin() * 1.7f64.exp();
+        assert!((f.derivative(0) - expected_dx).abs() < 1e-12);
+        assert!((f.derivative(1) - expected_dy).abs() < 1e-12);
+    }
+
+    #[test]
+    fn remainder_differentiates_both_operands() {
+        let tangents = Tangents::new(2);
+        // 5.1 % 1.3 = 5.1 - 3 * 1.3
+        let r = tangents.variable(5.1, 0) % tangents.variable(1.3, 1);
+        assert!((r.val - 1.2).abs() < 1e-12);
+        assert_eq!(r.gradient(), vec![1., -3.]);
+    }
+
+    #[test]
+    fn released_rows_are_reused() {
+        let tangents = Tangents::new(2);
+        let x = tangents.variable(2., 0);
+        let y = tangents.variable(3., 1);
+        let z = x * y;
+        assert_eq!(tangents.num_rows(), 3);
+
+        tangents.retain(&[z.row().unwrap()]);
+        let w = z * 2. + 1.;
+        assert_eq!(tangents.num_rows(), 3);
+        assert_eq!(w.gradient(), vec![6., 4.]);
+        assert_eq!(z.gradient(), vec![3., 2.]);
+    }
+
+    #[test]
+    #[should_panic(expected = "tangent row was used after being released")]
+    fn released_rows_can_not_be_used() {
+        let tangents = Tangents::new(1);
+        let x = tangents.variable(2., 0);
+        tangents.retain(&[]);
+        let _ = x * x;
+    }
+
+    #[test]
+    fn constants_have_no_gradient() {
+        let c: DynDual<f64> = DynDual::constant(2.);
+        let d = c.exp() * 3.;
+        assert!(d.gradient().is_empty());
+        assert_eq!(d.derivative(0), 0.);
+    }
+}
@@ -0,0 +1,135 @@
+//! Derivatives of arbitrary functions without seeding variables by hand
+//!
+//! Every helper evaluates the given closure once with its inputs seeded as variables, so variable
+//! `i` is the `i`-th element of the input array:
+//! ```
+//! # use deep_thought::autograd::*;
+//! # use ndarray::prelude::*;
+//! # use num_traits::Float;
+//! let x = array![1., 2.];
+//! // f(x, y) = x^2 y + sin(y)
+//! let g = grad(|[x, y]| x * x * y + y.sin(), &x);
+//! assert_eq!(g, array![4., 1. + 2f64.cos()]);
+//!
+//! let h = hessian(|[x, y]| x * x * y + y.sin(), &x);
+//! assert_eq!(h, array![[4., 2.], [2., -2f64.sin()]]);
+//! ```
+
+use crate::autograd::{Dual, HyperDual};
+use ndarray::prelude::*;
+use num_traits::Float;
+use std::array;
+
+/// Seed every element of `x` as a variable with its position as index
+fn variables<F: Float, D, const N: usize>(x: &Array1<F>, variable: fn(F, usize) -> D) -> [D; N] {
+    assert_eq!(x.len(), N, "expected {} inputs but found {}", N, x.len());
+    array::from_fn(|i| variable(x[i], i))
+}
+
+/// Gradient of the scalar function `f` at `x`
+pub fn grad<F, G, const N: usize>(f: G, x: &Array1<F>) -> Array1<F>
+where
+    F: Float,
+    G: Fn([Dual<F, N>; N]) -> Dual<F, N>,
+{
+    Array1::from(f(variables(x, Dual::variable)).e.to_vec())
+}
+
+/// Jacobian of the vector valued function `f` at `x`, where row `i` is the gradient of output `i`
+pub fn jacobian<F, G, O, const N: usize>(f: G, x: &Array1<F>) -> Array2<F>
+where
+    F: Float,
+    G: Fn([Dual<F, N>; N]) -> O,
+    O: IntoIterator<Item = Dual<F, N>>,
+{
+    let outputs: Vec<_> = f(variables(x, Dual::variable)).into_iter().collect();
+    Array2::from_shape_fn((outputs.len(), N), |(i, j)| outputs[i].e[j])
+}
+
+/// Hessian of the scalar function `f` at `x`
+pub fn hessian<F, G, const N: usize>(f: G, x: &Array1<F>) -> Array2<F>
+where
+    F: Float,
+    G: Fn([HyperDual<F, N>; N]) -> HyperDual<F, N>,
+{
+    let h = f(variables(x, HyperDual::variable)).h;
+    Array2::from_shape_fn((N, N), |(i, j)| h[i][j])
+}
+
+/// Product of the Hessian of the scalar function `f` at `x` with the vector `v`
+///
+/// `f` is evaluated forward-over-forward: the outer dual numbers differentiate with respect to
+/// every input, while the inner ones carry the directional derivative along `v`. Every operation
+/// therefore costs O(N) instead of the O(N²) of propagating the full [`hessian`].
+pub fn hvp<F, G, const N: usize>(f: G, x: &Array1<F>, v: &Array1<F>) -> Array1<F>
+where
+    F: Float,
+    G: Fn([Dual<Dual<F, 1>, N>; N]) -> Dual<Dual<F, 1>, N>,
+{
+    assert_eq!(v.len(), N, "expected a vector of length {}", N);
+    let mut inputs: [Dual<Dual<F, 1>, N>; N] =
+        variables(x, |x, i| Dual::variable(Dual::constant(x), i));
+    for (input, &v) in inputs.iter_mut().zip(v.iter()) {
+        input.val.e = [v];
+    }
+    Array1::from(f(inputs).e.map(|d| d.e[0]).to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jacobian_of_polar_coordinates() {
+        let (r, phi) = (2., 0.3f64);
+        let j = jacobian(|[r, phi]| [r * phi.cos(), r * phi.sin()], &array![r, phi]);
+        let expected = array![[phi.cos(), -r * phi.sin()], [phi.sin(), r * phi.cos()]];
+        assert_eq!(j, expected);
+
+        // jacobians of scalar functions equal their gradient
+        let f = |[x, y]: [Dual<f64, 2>; 2]| vec![x.exp() * y];
+        assert_eq!(
+            jacobian(f, &array![r, phi]).row(0),
+            grad(|x| f(x)[0], &array![r, phi])
+        );
+    }
+
+    fn rosenbrock<D: Float>([x, y]: [D; 2]) -> D {
+        (D::one() - x).powi(2) + (y - x * x).powi(2) * D::from(100.).unwrap()
+    }
+
+    #[test]
+    fn hvp_matches_hessian() {
+        let x = array![-0.5, 1.5];
+        let v = array![0.3, -2.];
+        let expected = hessian(rosenbrock, &x).dot(&v);
+        for (a, b) in hvp(rosenbrock, &x, &v).iter().zip(&expected) {
+            assert!((a - b).abs() < 1e-12);
+        }
+
+        // f(x, y, z) = x y z has a Hessian of [[0, z, y], [z, 0, x], [y, x, 0]]
+        let product = hvp(
+            |[x, y, z]| x * y * z,
+            &array![1., 2., 3.],
+            &array![1., 0., -1.],
+        );
+        assert_eq!(product, array![-2., 2., 2.]);
+    }
+
+    #[test]
+    fn gradient_vanishes_at_minimum() {
+        assert_eq!(
+            grad(
+                |[x, y]| (-x + 1.).powi(2) + (y - x * x).powi(2) * 100.,
+                &array![1., 1.]
+            ),
+            array![0., 0.]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 2 inputs but found 3")]
+    fn wrong_number_of_inputs() {
+        grad(|[x, y]| x * y, &array![1., 2., 3.]);
+    }
+}
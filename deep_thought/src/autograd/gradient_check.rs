@@ -0,0 +1,247 @@
+//! Verify forward-mode derivatives against central finite differences
+
+use crate::autograd::Dual;
+use num_traits::Float;
+use std::array;
+
+/// Result of comparing the derivatives of a [`Dual`] function with central finite differences
+#[derive(Debug, Clone, Copy)]
+pub struct GradientCheck<F, const N: usize> {
+    /// value of the function at the checked point
+    pub value: F,
+    /// derivatives computed with dual numbers
+    pub analytic: [F; N],
+    /// derivatives approximated with central finite differences
+    pub numeric: [F; N],
+    /// `|analytic - numeric|` for every component
+    pub abs_error: [F; N],
+    /// absolute error divided by the larger magnitude of both derivatives,
+    /// zero if both derivatives are zero
+    pub rel_error: [F; N],
+}
+
+impl<F: Float, const N: usize> GradientCheck<F, N> {
+    /// Largest absolute error over all components
+    pub fn max_abs_error(&self) -> F {
+        self.abs_error.iter().fold(F::zero(), |a, &b| a.max(b))
+    }
+
+    /// Largest relative error over all components
+    pub fn max_rel_error(&self) -> F {
+        self.rel_error.iter().fold(F::zero(), |a, &b| a.max(b))
+    }
+
+    /// Whether every component is within `tolerance`, either in absolute or in relative terms
+    pub fn passes(&self, tolerance: F) -> bool {
+        self.abs_error
+            .iter()
+            .zip(self.rel_error.iter())
+            .all(|(&abs, &rel)| abs <= tolerance || rel <= tolerance)
+    }
+}
+
+/// Evaluate `f` at `x` once with dual numbers and compare the resulting derivatives with central
+/// finite differences
+///
+/// Variable `i` of `f` is seeded with index `i`. The step size of the finite differences is
+/// scaled with the magnitude of every component.
+/// ```
+/// # use deep_thought::autograd::check_gradient;
+/// # use num_traits::Float;
+/// let check = check_gradient(|[x, y]| x.sin() * y.exp(), [0.5, 1.5]);
+/// assert!(check.passes(1e-8));
+/// ```
+pub fn check_gradient<F, G, const N: usize>(f: G, x: [F; N]) -> GradientCheck<F, N>
+where
+    F: Float,
+    G: Fn([Dual<F, N>; N]) -> Dual<F, N>,
+{
+    let result = f(array::from_fn(|i| Dual::variable(x[i], i)));
+    let eval = |x: [F; N]| f(x.map(Dual::constant)).val;
+
+    let two = F::one() + F::one();
+    let numeric = array::from_fn(|i| {
+        let step = F::epsilon().cbrt() * x[i].abs().max(F::one());
+        let (mut forward, mut backward) = (x, x);
+        forward[i] = x[i] + step;
+        backward[i] = x[i] - step;
+        (eval(forward) - eval(backward)) / (two * step)
+    });
+    let abs_error = array::from_fn(|i| (result.e[i] - numeric[i]).abs());
+    let rel_error = array::from_fn(|i| {
+        let scale = result.e[i].abs().max(numeric[i].abs());
+        if scale.is_zero() {
+            F::zero()
+        } else {
+            abs_error[i] / scale
+        }
+    });
+
+    GradientCheck {
+        value: result.val,
+        analytic: result.e,
+        numeric,
+        abs_error,
+        rel_error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: f64 = 1e-7;
+
+    fn assert_unary(name: &str, f: fn(Dual<f64, 1>) -> Dual<f64, 1>, points: &[f64]) {
+        for &x in points {
+            let check = check_gradient(|[x]| f(x), [x]);
+            assert!(check.passes(TOLERANCE), "{} at {}: {:?}", name, x, check);
+        }
+    }
+
+    fn assert_binary(
+        name: &str,
+        f: fn(Dual<f64, 2>, Dual<f64, 2>) -> Dual<f64, 2>,
+        points: &[[f64; 2]],
+    ) {
+        for &x in points {
+            let check = check_gradient(|[x, y]| f(x, y), x);
+            assert!(check.passes(TOLERANCE), "{} at {:?}: {:?}", name, x, check);
+        }
+    }
+
+    /// Apply a compound assignment to a copy of `x` and return it
+    macro_rules! assign {
+        ($x:ident $op:tt $rhs:expr) => {{
+            let mut x = $x;
+            x $op $rhs;
+            x
+        }};
+    }
+
+    #[test]
+    fn reports_wrong_derivatives() {
+        let check = check_gradient(|[x]| Dual::new(x.val * x.val, x.e), [3.]);
+        assert!(!check.passes(TOLERANCE));
+        assert!((check.numeric[0] - 6.).abs() < 1e-6);
+        assert_eq!(check.analytic, [1.]);
+        assert!((check.max_abs_error() - 5.).abs() < 1e-6);
+        assert!((check.max_rel_error() - 5. / 6.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let points = [[0.7, -1.3], [2.5, 0.4]];
+        assert_binary("add", |x, y| x + y, &points);
+        assert_binary("sub", |x, y| x - y, &points);
+        assert_binary("mul", |x, y| x * y, &points);
+        assert_binary("div", |x, y| x / y, &points);
+        assert_binary("neg", |x, _| -x, &points);
+
+        assert_unary("real + dual", |x| 2. + x, &[0.3]);
+        assert_unary("real - dual", |x| 2. - x, &[0.3]);
+        assert_unary("real * dual", |x| 2. * x, &[0.3]);
+        assert_unary("real / dual", |x| 2. / x, &[0.3, -1.7]);
+        assert_unary("dual + real", |x| x + 2., &[0.3]);
+        assert_unary("dual - real", |x| x - 2., &[0.3]);
+        assert_unary("dual * real", |x| x * 2., &[0.3]);
+        assert_unary("dual / real", |x| x / 2., &[0.3]);
+        assert_unary("dual % real", |x| x % 2., &[0.3, 5.1]);
+        assert_unary("real % dual", |x| 2. % x, &[0.3, -0.7]);
+        assert_binary("rem", |x, y| x % y, &[[2.5, 0.4], [-5.1, 1.3], [0.7, -0.3]]);
+    }
+
+    #[test]
+    fn op_assign() {
+        let points = [[0.7, -1.3], [2.5, 0.4]];
+        assert_binary("+=", |x, y| assign!(x += y), &points);
+        assert_binary("-=", |x, y| assign!(x -= y), &points);
+        assert_binary("*=", |x, y| assign!(x *= y), &points);
+        assert_binary("/=", |x, y| assign!(x /= y), &points);
+        assert_binary(
+            "%=",
+            |x, y| assign!(x %= y),
+            &[[2.5, 0.4], [-5.1, 1.3], [0.7, -0.3]],
+        );
+
+        assert_unary("+= real", |x| assign!(x += 2.), &[0.3]);
+        assert_unary("-= real", |x| assign!(x -= 2.), &[0.3]);
+        assert_unary("*= real", |x| assign!(x *= 2.), &[0.3]);
+        assert_unary("/= real", |x| assign!(x /= 2.), &[0.3]);
+        assert_unary("%= real", |x| assign!(x %= 2.), &[0.3, 5.1]);
+    }
+
+    #[test]
+    fn unary_float_methods() {
+        let points = [-1.7, -0.4, 0.3, 2.2];
+        assert_unary("fract", Float::fract, &points);
+        assert_unary("abs", Float::abs, &points);
+        assert_unary("signum", Float::signum, &points);
+        assert_unary("floor", Float::floor, &points);
+        assert_unary("ceil", Float::ceil, &points);
+        assert_unary("round", Float::round, &points);
+        assert_unary("trunc", Float::trunc, &points);
+        assert_unary("recip", Float::recip, &points);
+        assert_unary("powi", |x| x.powi(3), &points);
+        assert_unary("powi negative", |x| x.powi(-2), &points);
+        assert_unary("exp", Float::exp, &points);
+        assert_unary("exp2", Float::exp2, &points);
+        assert_unary("cbrt", Float::cbrt, &points);
+        assert_unary("sin", Float::sin, &points);
+        assert_unary("cos", Float::cos, &points);
+        assert_unary("tan", Float::tan, &points);
+        assert_unary("sin_cos", |x| x.sin_cos().0 * x.sin_cos().1, &points);
+        assert_unary("atan", Float::atan, &points);
+        assert_unary("exp_m1", Float::exp_m1, &points);
+        assert_unary("sinh", Float::sinh, &points);
+        assert_unary("cosh", Float::cosh, &points);
+        assert_unary("tanh", Float::tanh, &points);
+        assert_unary("asinh", Float::asinh, &points);
+    }
+
+    #[test]
+    fn restricted_domain_float_methods() {
+        let positive = [0.3, 2.2];
+        assert_unary("sqrt", Float::sqrt, &positive);
+        assert_unary("ln", Float::ln, &positive);
+        assert_unary("log2", Float::log2, &positive);
+        assert_unary("log10", Float::log10, &positive);
+        assert_unary("ln_1p", Float::ln_1p, &[-0.4, 0.3, 2.2]);
+        assert_unary(
+            "powf constant exponent",
+            |x| x.powf(Dual::constant(1.5)),
+            &positive,
+        );
+        assert_unary(
+            "powf negative base",
+            |x| x.powf(Dual::constant(2.)),
+            &[-1.3],
+        );
+
+        let unit = [-0.6, 0.3];
+        assert_unary("asin", Float::asin, &unit);
+        assert_unary("acos", Float::acos, &unit);
+        assert_unary("atanh", Float::atanh, &unit);
+        assert_unary("acosh", Float::acosh, &[1.3, 2.2]);
+    }
+
+    #[test]
+    fn binary_float_methods() {
+        let points = [[0.7, -1.3], [2.5, 0.4], [-0.8, 1.1]];
+        assert_binary("max", Float::max, &points);
+        assert_binary("min", Float::min, &points);
+        #[allow(deprecated)]
+        {
+            assert_binary("abs_sub", Float::abs_sub, &points);
+        }
+        assert_binary("hypot", Float::hypot, &points);
+        assert_binary("atan2", Float::atan2, &points);
+
+        let positive = [[0.7, 1.3], [2.5, 0.4]];
+        assert_binary("powf", Float::powf, &positive);
+        assert_binary("log", Float::log, &[[0.7, 3.], [2.5, 0.4]]);
+
+        let check = check_gradient(|[x, a, b]| x.mul_add(a, b), [0.7, -1.3, 2.1]);
+        assert!(check.passes(TOLERANCE), "mul_add: {:?}", check);
+    }
+}
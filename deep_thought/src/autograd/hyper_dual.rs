@@ -0,0 +1,652 @@
+//! Hyper-dual numbers for exact second derivatives
+//!
+//! A [`HyperDual<F, N>`] carries the gradient and the full Hessian with respect to `N` variables
+//! through every operation. Since the Hessian grows quadratically with `N`, this is only meant
+//! for small problems, like Newton steps or curvature diagnostics on small networks:
+//! ```
+//! # use deep_thought::prelude::*;
+//! # use ndarray::prelude::*;
+//! let net = NeuralNetwork::<f64, 3>::new().add_layer(Layer::new(2, 1).activation(Activation::Tanh));
+//! let inp = array![[1.], [2.]].map(|&x| HyperDual::constant(x));
+//! let out = net.forward_with(&inp, HyperDual::<f64, 3>::variable)[[0, 0]];
+//! // the hessian of a single neuron is symmetric
+//! assert_eq!(out.h[0][1], out.h[1][0]);
+//! ```
+
+use num_traits::*;
+use std::array;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::*;
+
+/// A dual number tracking first and second derivatives with respect to `N` variables
+#[derive(Debug, Clone, Copy)]
+pub struct HyperDual<F, const N: usize> {
+    /// real value
+    pub val: F,
+    /// first derivatives (gradient)
+    pub e: [F; N],
+    /// second derivatives (Hessian), `h[i][j]` is the derivative with respect to variables `i` and `j`
+    pub h: [[F; N]; N],
+}
+
+impl<F: Num + Copy, const N: usize> HyperDual<F, N> {
+    /// Create a constant hyper-dual number, meaning all of its derivatives are zero
+    pub fn constant(val: F) -> Self {
+        HyperDual {
+            val,
+            e: [F::zero(); N],
+            h: [[F::zero(); N]; N],
+        }
+    }
+
+    /// Create a variable hyper-dual number, meaning it has a derivative
+    /// of one. Every variable must be assigned a unique index
+    pub fn variable(val: F, index: usize) -> Self {
+        let mut result = Self::constant(val);
+        result.e[index] = F::one();
+        result
+    }
+
+    /// Apply a function to the real part, given its first and second derivative at `self.val`
+    fn chain(self, val: F, d1: F, d2: F) -> Self {
+        HyperDual {
+            val,
+            e: self.e.map(|x| d1 * x),
+            h: array::from_fn(|i| {
+                array::from_fn(|j| d1 * self.h[i][j] + d2 * self.e[i] * self.e[j])
+            }),
+        }
+    }
+
+    /// Apply a binary function to the real parts, given its partial derivatives
+    /// `d = [d/dx, d/dy]` and `dd = [d²/dx², d²/dxdy, d²/dy²]` at `(self.val, other.val)`
+    fn chain2(self, other: Self, val: F, d: [F; 2], dd: [F; 3]) -> Self {
+        let (a, b) = (self, other);
+        HyperDual {
+            val,
+            e: array::from_fn(|i| d[0] * a.e[i] + d[1] * b.e[i]),
+            h: array::from_fn(|i| {
+                array::from_fn(|j| {
+                    d[0] * a.h[i][j]
+                        + d[1] * b.h[i][j]
+                        + dd[0] * a.e[i] * a.e[j]
+                        + dd[1] * (a.e[i] * b.e[j] + b.e[i] * a.e[j])
+                        + dd[2] * b.e[i] * b.e[j]
+                })
+            }),
+        }
+    }
+
+    /// Whether all derivatives are zero
+    fn has_zero_derivatives(&self) -> bool {
+        self.e.iter().all(|x| x.is_zero()) && self.h.iter().flatten().all(|x| x.is_zero())
+    }
+}
+
+impl<F: Num + Copy + fmt::Debug, const N: usize> fmt::Display for HyperDual<F, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HyperDual")
+            .field("val", &self.val)
+            .field("e", &self.e)
+            .field("h", &self.h)
+            .finish()
+    }
+}
+
+impl<F: Num + Copy, const N: usize> From<F> for HyperDual<F, N> {
+    #[inline]
+    fn from(x: F) -> Self {
+        Self::constant(x)
+    }
+}
+
+impl<F: Num + Copy, const N: usize> Add for HyperDual<F, N> {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, other: Self) -> Self::Output {
+        let (one, zero) = (F::one(), F::zero());
+        self.chain2(other, self.val + other.val, [one, one], [zero; 3])
+    }
+}
+
+impl<F: Num + Copy + Neg<Output = F>, const N: usize> Sub for HyperDual<F, N> {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, other: Self) -> Self::Output {
+        let (one, zero) = (F::one(), F::zero());
+        self.chain2(other, self.val - other.val, [one, -one], [zero; 3])
+    }
+}
+
+impl<F: Num + Copy, const N: usize> Mul for HyperDual<F, N> {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, other: Self) -> Self::Output {
+        let (one, zero) = (F::one(), F::zero());
+        let val = self.val * other.val;
+        self.chain2(other, val, [other.val, self.val], [zero, one, zero])
+    }
+}
+
+impl<F: Num + Copy + Neg<Output = F>, const N: usize> Div for HyperDual<F, N> {
+    type Output = Self;
+
+    #[inline]
+    fn div(self, other: Self) -> Self::Output {
+        let (x, y) = (self.val, other.val);
+        let y2 = y * y;
+        let two = F::one() + F::one();
+        self.chain2(
+            other,
+            x / y,
+            [F::one() / y, -x / y2],
+            [F::zero(), -F::one() / y2, two * x / (y2 * y)],
+        )
+    }
+}
+
+impl<F: Num + Copy + Neg<Output = F>, const N: usize> Rem for HyperDual<F, N> {
+    type Output = Self;
+
+    // x % y = x - trunc(x / y) * y, whose quotient is locally constant
+    #[inline]
+    fn rem(self, other: Self) -> Self::Output {
+        let val = self.val % other.val;
+        let quotient = (self.val - val) / other.val;
+        self.chain2(other, val, [F::one(), -quotient], [F::zero(); 3])
+    }
+}
+
+impl<F: Num + Copy + Neg<Output = F>, const N: usize> Neg for HyperDual<F, N> {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self::Output {
+        self.chain(-self.val, -F::one(), F::zero())
+    }
+}
+
+macro_rules! real_arithmetic {
+    ($($imp:ident::$method:ident),*) => {
+        $(
+            impl<F: Num + Copy + Neg<Output = F>, const N: usize> $imp<F> for HyperDual<F, N> {
+                type Output = Self;
+
+                #[inline]
+                fn $method(self, other: F) -> Self::Output {
+                    self.$method(HyperDual::constant(other))
+                }
+            }
+        )*
+    };
+}
+
+real_arithmetic!(Add::add, Sub::sub, Mul::mul, Div::div, Rem::rem);
+
+macro_rules! op_assign {
+    ($($imp:ident::$method:ident => $op:ident),*) => {
+        $(
+            impl<F: Num + Copy + Neg<Output = F>, const N: usize> $imp for HyperDual<F, N> {
+                #[inline]
+                fn $method(&mut self, other: Self) {
+                    *self = (*self).$op(other);
+                }
+            }
+
+            impl<F: Num + Copy + Neg<Output = F>, const N: usize> $imp<F> for HyperDual<F, N> {
+                #[inline]
+                fn $method(&mut self, other: F) {
+                    *self = (*self).$op(other);
+                }
+            }
+        )*
+    };
+}
+
+op_assign!(
+    AddAssign::add_assign => add,
+    SubAssign::sub_assign => sub,
+    MulAssign::mul_assign => mul,
+    DivAssign::div_assign => div,
+    RemAssign::rem_assign => rem
+);
+
+impl<F: Num + Copy, const N: usize> Zero for HyperDual<F, N> {
+    fn zero() -> Self {
+        HyperDual::constant(F::zero())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.val.is_zero() && self.has_zero_derivatives()
+    }
+}
+
+impl<F: Num + Copy, const N: usize> One for HyperDual<F, N> {
+    fn one() -> Self {
+        HyperDual::constant(F::one())
+    }
+
+    fn is_one(&self) -> bool {
+        self.val.is_one() && self.has_zero_derivatives()
+    }
+}
+
+impl<F: PartialEq, const N: usize> PartialEq for HyperDual<F, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.val == other.val
+    }
+}
+
+impl<F: PartialOrd, const N: usize> PartialOrd for HyperDual<F, N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.val.partial_cmp(&other.val)
+    }
+}
+
+impl<F: Num + Copy + Neg<Output = F>, const N: usize> Num for HyperDual<F, N> {
+    type FromStrRadixErr = <F as Num>::FromStrRadixErr;
+    fn from_str_radix(string: &str, radix: u32) -> Result<Self, <Self as Num>::FromStrRadixErr> {
+        Ok(HyperDual::constant(F::from_str_radix(string, radix)?))
+    }
+}
+
+macro_rules! impl_to_primitive {
+    ($($ty:ty, $to:ident);*) => {
+        $(
+            #[inline]
+            fn $to(&self) -> Option<$ty> {
+                self.val.$to()
+            }
+        )*
+    };
+}
+
+// Clips the non-real part
+impl<F: ToPrimitive, const N: usize> ToPrimitive for HyperDual<F, N> {
+    impl_to_primitive!(
+        usize, to_usize; isize, to_isize; u8, to_u8; u16, to_u16; u32, to_u32; u64, to_u64;
+        i8, to_i8; i16, to_i16; i32, to_i32; i64, to_i64; u128, to_u128; i128, to_i128;
+        f32, to_f32; f64, to_f64
+    );
+}
+
+macro_rules! impl_from_primitive {
+    ($($ty:ty, $from:ident);*) => {
+        $(
+            #[inline]
+            fn $from(n: $ty) -> Option<Self> {
+                Some(HyperDual::constant(F::$from(n)?))
+            }
+        )*
+    };
+}
+
+impl<F: FromPrimitive + Num + Copy, const N: usize> FromPrimitive for HyperDual<F, N> {
+    impl_from_primitive!(
+        usize, from_usize; isize, from_isize; u8, from_u8; u16, from_u16; u32, from_u32;
+        u64, from_u64; i8, from_i8; i16, from_i16; i32, from_i32; i64, from_i64;
+        u128, from_u128; i128, from_i128; f32, from_f32; f64, from_f64
+    );
+}
+
+impl<F: NumCast + Num + Copy, const N: usize> NumCast for HyperDual<F, N> {
+    fn from<U: ToPrimitive>(n: U) -> Option<Self> {
+        Some(HyperDual::constant(<F as NumCast>::from(n)?))
+    }
+}
+
+macro_rules! float_impl_basic {
+    ($($name:ident),*) => {
+        $(fn $name() -> Self {
+            HyperDual::constant(F::$name())
+        })*
+    }
+}
+
+macro_rules! float_impl_passthrough {
+    ($result:ty, $($name:ident),*) => {
+        $(fn $name(self) -> $result {
+            self.val.$name()
+        })*
+    }
+}
+
+macro_rules! float_impl_self_passthrough {
+    ($($name:ident),*) => {
+        $(fn $name(self) -> Self {
+            HyperDual::constant(self.val.$name())
+        })*
+    }
+}
+
+impl<F: Float, const N: usize> Float for HyperDual<F, N> {
+    float_impl_basic!(
+        nan,
+        infinity,
+        neg_infinity,
+        neg_zero,
+        min_value,
+        max_value,
+        min_positive_value
+    );
+    float_impl_passthrough!(
+        bool,
+        is_nan,
+        is_infinite,
+        is_finite,
+        is_normal,
+        is_sign_positive,
+        is_sign_negative
+    );
+    float_impl_passthrough!((u64, i16, i8), integer_decode);
+    float_impl_passthrough!(::std::num::FpCategory, classify);
+    float_impl_self_passthrough!(floor, ceil, round, trunc, signum);
+
+    fn fract(self) -> Self {
+        self.chain(self.val.fract(), F::one(), F::zero())
+    }
+
+    fn abs(self) -> Self {
+        self.chain(self.val.abs(), self.val.signum(), F::zero())
+    }
+
+    fn mul_add(self, a: Self, b: Self) -> Self {
+        HyperDual {
+            val: self.val.mul_add(a.val, b.val),
+            ..self * a + b
+        }
+    }
+
+    fn recip(self) -> Self {
+        let r = self.val.recip();
+        self.chain(r, -r * r, F::from(2).unwrap() * r * r * r)
+    }
+
+    fn powi(self, n: i32) -> Self {
+        let exp = F::from(n).unwrap();
+        let d1 = exp * self.val.powi(n - 1);
+        let d2 = exp * (exp - F::one()) * self.val.powi(n - 2);
+        self.chain(self.val.powi(n), d1, d2)
+    }
+
+    fn powf(self, n: Self) -> Self {
+        let (x, p) = (self.val, n.val);
+        let val = x.powf(p);
+        let d1 = p * x.powf(p - F::one());
+        let d2 = p * (p - F::one()) * x.powf(p - F::from(2).unwrap());
+        if n.has_zero_derivatives() {
+            // avoid ln(x) which is undefined for negative bases
+            return self.chain(val, d1, d2);
+        }
+        let ln = x.ln();
+        let d_xn = x.powf(p - F::one()) * (F::one() + p * ln);
+        self.chain2(n, val, [d1, val * ln], [d2, d_xn, val * ln * ln])
+    }
+
+    fn sqrt(self) -> Self {
+        let val = self.val.sqrt();
+        let d1 = (F::from(2).unwrap() * val).recip();
+        let d2 = -(F::from(4).unwrap() * self.val * val).recip();
+        self.chain(val, d1, d2)
+    }
+
+    fn exp(self) -> Self {
+        let val = self.val.exp();
+        self.chain(val, val, val)
+    }
+
+    fn exp2(self) -> Self {
+        let val = self.val.exp2();
+        let ln2 = F::from(2).unwrap().ln();
+        self.chain(val, val * ln2, val * ln2 * ln2)
+    }
+
+    fn ln(self) -> Self {
+        let r = self.val.recip();
+        self.chain(self.val.ln(), r, -r * r)
+    }
+
+    fn log(self, base: Self) -> Self {
+        self.ln() / base.ln()
+    }
+
+    fn log2(self) -> Self {
+        let ln2 = F::from(2).unwrap().ln();
+        let r = self.val.recip();
+        self.chain(self.val.log2(), r / ln2, -r * r / ln2)
+    }
+
+    fn log10(self) -> Self {
+        let ln10 = F::from(10).unwrap().ln();
+        let r = self.val.recip();
+        self.chain(self.val.log10(), r / ln10, -r * r / ln10)
+    }
+
+    fn max(self, other: Self) -> Self {
+        if self.val >= other.val {
+            self
+        } else {
+            other
+        }
+    }
+
+    fn min(self, other: Self) -> Self {
+        if self.val <= other.val {
+            self
+        } else {
+            other
+        }
+    }
+
+    fn abs_sub(self, other: Self) -> Self {
+        if self.val > other.val {
+            self - other
+        } else {
+            HyperDual::zero()
+        }
+    }
+
+    fn cbrt(self) -> Self {
+        let val = self.val.cbrt();
+        let d1 = (F::from(3).unwrap() * val.powi(2)).recip();
+        let d2 = -F::from(2).unwrap() / (F::from(9).unwrap() * val.powi(5));
+        self.chain(val, d1, d2)
+    }
+
+    fn hypot(self, other: Self) -> Self {
+        let (x, y) = (self.val, other.val);
+        let h = x.hypot(y);
+        let h3 = h * h * h;
+        self.chain2(
+            other,
+            h,
+            [x / h, y / h],
+            [y * y / h3, -x * y / h3, x * x / h3],
+        )
+    }
+
+    fn sin(self) -> Self {
+        let (sin, cos) = self.val.sin_cos();
+        self.chain(sin, cos, -sin)
+    }
+
+    fn cos(self) -> Self {
+        let (sin, cos) = self.val.sin_cos();
+        self.chain(cos, -sin, -cos)
+    }
+
+    fn tan(self) -> Self {
+        let tan = self.val.tan();
+        let sec2 = F::one() + tan * tan;
+        self.chain(tan, sec2, F::from(2).unwrap() * tan * sec2)
+    }
+
+    fn asin(self) -> Self {
+        let s = F::one() - self.val.powi(2);
+        self.chain(self.val.asin(), s.sqrt().recip(), self.val / (s * s.sqrt()))
+    }
+
+    fn acos(self) -> Self {
+        let s = F::one() - self.val.powi(2);
+        self.chain(
+            self.val.acos(),
+            -s.sqrt().recip(),
+            -self.val / (s * s.sqrt()),
+        )
+    }
+
+    fn atan(self) -> Self {
+        let s = F::one() + self.val.powi(2);
+        let d2 = -F::from(2).unwrap() * self.val / (s * s);
+        self.chain(self.val.atan(), s.recip(), d2)
+    }
+
+    fn atan2(self, other: Self) -> Self {
+        let (y, x) = (self.val, other.val);
+        let r = x * x + y * y;
+        let r2 = r * r;
+        let two = F::from(2).unwrap();
+        self.chain2(
+            other,
+            y.atan2(x),
+            [x / r, -y / r],
+            [-two * x * y / r2, (y * y - x * x) / r2, two * x * y / r2],
+        )
+    }
+
+    fn sin_cos(self) -> (Self, Self) {
+        (self.sin(), self.cos())
+    }
+
+    fn exp_m1(self) -> Self {
+        let exp = self.val.exp();
+        self.chain(self.val.exp_m1(), exp, exp)
+    }
+
+    fn ln_1p(self) -> Self {
+        let r = (F::one() + self.val).recip();
+        self.chain(self.val.ln_1p(), r, -r * r)
+    }
+
+    fn sinh(self) -> Self {
+        let (sinh, cosh) = (self.val.sinh(), self.val.cosh());
+        self.chain(sinh, cosh, sinh)
+    }
+
+    fn cosh(self) -> Self {
+        let (sinh, cosh) = (self.val.sinh(), self.val.cosh());
+        self.chain(cosh, sinh, cosh)
+    }
+
+    fn tanh(self) -> Self {
+        let tanh = self.val.tanh();
+        let d1 = F::one() - tanh * tanh;
+        self.chain(tanh, d1, -F::from(2).unwrap() * tanh * d1)
+    }
+
+    fn asinh(self) -> Self {
+        let s = self.val.powi(2) + F::one();
+        self.chain(
+            self.val.asinh(),
+            s.sqrt().recip(),
+            -self.val / (s * s.sqrt()),
+        )
+    }
+
+    fn acosh(self) -> Self {
+        let s = self.val.powi(2) - F::one();
+        self.chain(
+            self.val.acosh(),
+            s.sqrt().recip(),
+            -self.val / (s * s.sqrt()),
+        )
+    }
+
+    fn atanh(self) -> Self {
+        let s = F::one() - self.val.powi(2);
+        let d2 = F::from(2).unwrap() * self.val / (s * s);
+        self.chain(self.val.atanh(), s.recip(), d2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hessian_of_polynomial() {
+        // f(x, y) = x^3 y + 2 x y^2
+        let x = HyperDual::<f64, 2>::variable(2., 0);
+        let y = HyperDual::<f64, 2>::variable(3., 1);
+        let f = x.powi(3) * y + x * y * y * 2.;
+
+        assert_eq!(f.val, 60.);
+        assert_eq!(f.e, [3. * 4. * 3. + 2. * 9., 8. + 4. * 2. * 3.]);
+        assert_eq!(
+            f.h,
+            [
+                [6. * 2. * 3., 3. * 4. + 4. * 3.],
+                [3. * 4. + 4. * 3., 4. * 2.]
+            ]
+        );
+    }
+
+    #[test]
+    fn remainder_is_linear_between_discontinuities() {
+        // 5.1 % 1.3 = 5.1 - 3 * 1.3
+        let x = HyperDual::<f64, 2>::variable(5.1, 0);
+        let y = HyperDual::<f64, 2>::variable(1.3, 1);
+        let r = x % y;
+        assert_eq!(r.e, [1., -3.]);
+        assert_eq!(r.h, [[0.; 2]; 2]);
+    }
+
+    type Function = fn(HyperDual<f64, 1>) -> HyperDual<f64, 1>;
+
+    #[test]
+    fn zero_checks_include_second_derivatives() {
+        let x = HyperDual::<f64, 1>::variable(0., 0);
+        // x² has a vanishing gradient at zero but a second derivative of 2
+        let square = x * x;
+        assert_eq!(square.e, [0.]);
+        assert!(!square.is_zero());
+        assert!(!(square + 1.).is_one());
+        assert!(HyperDual::<f64, 1>::constant(0.).is_zero());
+    }
+
+    #[test]
+    fn second_derivatives_match_finite_differences() {
+        let functions: [(&str, Function); 12] = [
+            ("sqrt", |x| x.sqrt()),
+            ("exp", |x| x.exp()),
+            ("ln", |x| x.ln()),
+            ("recip", |x| x.recip()),
+            ("tanh", |x| x.tanh()),
+            ("atan", |x| x.atan()),
+            ("asin", |x| x.asin()),
+            ("cbrt", |x| x.cbrt()),
+            ("log10", |x| x.log10()),
+            ("atanh", |x| x.atanh()),
+            ("powf", |x| x.powf(x)),
+            ("hypot", |x| x.hypot(x * x)),
+        ];
+        let x = 0.4;
+        let h = 1e-4;
+        for (name, f) in functions.iter() {
+            let first = |x: f64| f(HyperDual::variable(x, 0)).e[0];
+            let expected = (first(x + h) - first(x - h)) / (2. * h);
+            let found = f(HyperDual::variable(x, 0)).h[0][0];
+            assert!(
+                (found - expected).abs() < 1e-6 * expected.abs().max(1.),
+                "{}: {} != {}",
+                name,
+                found,
+                expected
+            );
+        }
+    }
+}
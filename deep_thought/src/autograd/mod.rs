@@ -1,7 +1,20 @@
+mod array_zip;
 mod dual;
 mod dual_cast;
 mod dual_rand;
+mod dyn_dual;
+mod functional;
+mod gradient_check;
+mod hyper_dual;
+mod sparse_dual;
+mod tape;
 
+pub(crate) use array_zip::ArrayZip;
 pub use dual::*;
-pub use dual_cast::*;
 pub use dual_rand::*;
+pub use dyn_dual::*;
+pub use functional::*;
+pub use gradient_check::*;
+pub use hyper_dual::*;
+pub use sparse_dual::*;
+pub use tape::*;
@@ -0,0 +1,182 @@
+//! Sparse tangent storage for [`DynDual`] numbers
+//!
+//! Within a neural network, most values only depend on a small fraction of the parameters
+//! (an input to the first layer only depends on a single row of its weights). Storing only
+//! the nonzero derivatives makes memory usage and runtime scale with the actual dependencies
+//! instead of the total number of parameters. Rows released through [`TangentBuffer::retain`]
+//! are no longer counted and their storage is reused by later rows.
+
+use crate::autograd::{DynDual, Rows, TangentBuffer, TangentRow};
+use num_traits::*;
+use std::cell::RefCell;
+
+/// A dual number which only stores its nonzero derivatives
+pub type SparseDual<'t, F> = DynDual<'t, F, SparseTangents<F>>;
+
+/// Sparse [`TangentBuffer`], every row stores (index, derivative) pairs sorted by index.
+/// Derivatives which are not stored are zero.
+pub struct SparseTangents<F> {
+    num_variables: usize,
+    rows: RefCell<Rows<Vec<(usize, F)>>>,
+}
+
+impl<F: Num + Copy> SparseTangents<F> {
+    /// Create an empty tangent buffer tracking derivatives with respect to `num_variables` variables
+    pub fn new(num_variables: usize) -> Self {
+        SparseTangents {
+            num_variables,
+            rows: RefCell::new(Rows::new()),
+        }
+    }
+
+    /// Total number of stored (nonzero) derivatives across all rows which have not been released
+    /// by [`TangentBuffer::retain`]
+    pub fn num_stored(&self) -> usize {
+        self.rows.borrow().iter().map(Vec::len).sum()
+    }
+
+    /// Store a new row, `fill` appends its (index, derivative) pairs sorted by index given the
+    /// existing rows
+    fn push(&self, fill: impl FnOnce(&Rows<Vec<(usize, F)>>, &mut Vec<(usize, F)>)) -> TangentRow {
+        self.rows.borrow_mut().insert(|rows, row| {
+            row.clear();
+            fill(rows, row);
+        })
+    }
+}
+
+impl<F: Num + Copy> TangentBuffer<F> for SparseTangents<F> {
+    type Row = TangentRow;
+
+    fn num_variables(&self) -> usize {
+        self.num_variables
+    }
+
+    fn seed(&self, index: usize) -> TangentRow {
+        self.push(|_, row| row.push((index, F::one())))
+    }
+
+    fn scale(&self, row: TangentRow, factor: F) -> TangentRow {
+        if factor.is_zero() {
+            return self.push(|_, _| ());
+        }
+        self.push(|rows, out| out.extend(rows.get(row).iter().map(|&(i, x)| (i, x * factor))))
+    }
+
+    fn combine(&self, a: TangentRow, b: TangentRow, factor_a: F, factor_b: F) -> TangentRow {
+        self.push(|rows, merged| {
+            let (mut a, mut b) = (rows.get(a).iter().peekable(), rows.get(b).iter().peekable());
+            loop {
+                let entry = match (a.peek(), b.peek()) {
+                    (Some(&&(i, x)), Some(&&(j, y))) if i == j => {
+                        a.next();
+                        b.next();
+                        (i, x * factor_a + y * factor_b)
+                    }
+                    (Some(&&(i, x)), Some(&&(j, _))) if i < j => {
+                        a.next();
+                        (i, x * factor_a)
+                    }
+                    (_, Some(&&(j, y))) => {
+                        b.next();
+                        (j, y * factor_b)
+                    }
+                    (Some(&&(i, x)), None) => {
+                        a.next();
+                        (i, x * factor_a)
+                    }
+                    (None, None) => break,
+                };
+                merged.push(entry);
+            }
+        })
+    }
+
+    fn derivative(&self, row: TangentRow, index: usize) -> F {
+        let rows = self.rows.borrow();
+        let entries = rows.get(row);
+        match entries.binary_search_by_key(&index, |&(i, _)| i) {
+            Ok(position) => entries[position].1,
+            Err(_) => F::zero(),
+        }
+    }
+
+    fn gradient(&self, row: TangentRow) -> Vec<F> {
+        let mut gradient = vec![F::zero(); self.num_variables];
+        for &(index, value) in self.rows.borrow().get(row) {
+            gradient[index] = value;
+        }
+        gradient
+    }
+
+    fn is_constant(&self, row: TangentRow) -> bool {
+        self.rows.borrow().get(row).iter().all(|(_, x)| x.is_zero())
+    }
+
+    fn retain(&self, live: &[TangentRow]) {
+        self.rows.borrow_mut().retain(live)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::autograd::Tangents;
+
+    #[test]
+    fn sparse_and_dense_tangents_agree() {
+        let dense = Tangents::new(4);
+        let sparse = SparseTangents::new(4);
+
+        fn f<D: Float>(x: D, y: D) -> D {
+            (x * y).sin() + y.ln() / x
+        }
+
+        let out_sparse = f(sparse.variable(0.7, 3), sparse.variable(1.3, 1));
+        let out_dense = f(dense.variable(0.7, 3), dense.variable(1.3, 1));
+
+        assert_eq!(out_sparse.val, out_dense.val);
+        assert_eq!(out_sparse.gradient(), out_dense.gradient());
+        assert_eq!(out_sparse.derivative(0), 0.);
+        assert_eq!(out_sparse.derivative(1), out_dense.derivative(1));
+    }
+
+    #[test]
+    fn only_nonzero_derivatives_are_stored() {
+        let sparse = SparseTangents::new(1000);
+        let x = sparse.variable(2., 10);
+        let y = sparse.variable(3., 500);
+        let before = sparse.num_stored();
+
+        // one entry for x * 2 and two for the sum
+        let _ = x * 2. + y;
+        assert_eq!(sparse.num_stored() - before, 3);
+    }
+
+    #[test]
+    fn num_stored_only_counts_rows_of_live_values() {
+        use crate::activation::Activation;
+        use crate::neural_network::Layer;
+        use ndarray::prelude::*;
+
+        let layers: Vec<Layer<f64>> = (0..4)
+            .map(|_| Layer::new(3, 3).activation(Activation::Tanh))
+            .collect();
+        let num_parameters: usize = layers.iter().map(Layer::num_parameters).sum();
+        let sparse = SparseTangents::new(num_parameters);
+
+        let mut input = array![[1.], [2.], [3.]].map(|&x| SparseDual::constant(x));
+        let mut offset = 0;
+        for layer in &layers {
+            input = layer.forward_with(&input, offset, |val, index| sparse.variable(val, index));
+            let peak = sparse.num_stored();
+
+            let live: Vec<_> = input.iter().filter_map(SparseDual::row).collect();
+            sparse.retain(&live);
+            // every output depends on its row of weights, its bias and every earlier parameter
+            assert_eq!(sparse.num_stored(), 3 * (3 + 1 + offset));
+            assert!(sparse.num_stored() < peak);
+            offset += layer.num_parameters();
+        }
+    }
+}
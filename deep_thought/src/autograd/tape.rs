@@ -0,0 +1,212 @@
+//! Reverse-mode automatic differentiation
+//!
+//! Forward-mode dual numbers carry one derivative per variable through every operation, so their
+//! cost grows linearly with the number of variables. A [`Tape`] instead records every operation
+//! together with its local partial derivatives and computes the derivatives with respect to all
+//! variables in a single backward sweep once the result is known.
+
+use crate::autograd::{DynDual, TangentBuffer};
+use num_traits::*;
+use std::cell::RefCell;
+
+/// A number whose operations are recorded on a [`Tape`].
+/// Calling [`DynDual::gradient`] on it performs the backward pass.
+pub type Var<'t, F> = DynDual<'t, F, Tape<F>>;
+
+/// A single recorded operation
+#[derive(Clone, Copy, Debug)]
+enum Node<F> {
+    /// The variable with the given index
+    Leaf(usize),
+    /// Result of a unary operation with the given (parent, partial derivative)
+    Unary(usize, F),
+    /// Result of a binary operation with the given (parent, partial derivative) pairs
+    Binary(usize, F, usize, F),
+}
+
+/// Records operations on [`Var`]s in evaluation order
+pub struct Tape<F> {
+    num_variables: usize,
+    nodes: RefCell<Vec<Node<F>>>,
+    /// whether each node's derivatives are zero because of the recorded partial derivatives
+    constant: RefCell<Vec<bool>>,
+    /// (node, gradient) of the most recent backward sweep, nodes are never modified once recorded
+    /// so it stays valid while the tape grows
+    cache: RefCell<Option<(usize, Vec<F>)>>,
+}
+
+impl<F: Num + Copy> Tape<F> {
+    /// Create an empty tape computing derivatives with respect to `num_variables` variables
+    pub fn new(num_variables: usize) -> Self {
+        Tape {
+            num_variables,
+            nodes: RefCell::new(vec![]),
+            constant: RefCell::new(vec![]),
+            cache: RefCell::new(None),
+        }
+    }
+
+    /// Number of operations recorded so far
+    pub fn len(&self) -> usize {
+        self.nodes.borrow().len()
+    }
+
+    /// Whether no operations have been recorded yet
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn push(&self, node: Node<F>) -> usize {
+        let mut constant = self.constant.borrow_mut();
+        let vanishes = |parent: usize, d: F| d.is_zero() || constant[parent];
+        let is_constant = match node {
+            Node::Leaf(_) => false,
+            Node::Unary(parent, d) => vanishes(parent, d),
+            Node::Binary(a, d_a, b, d_b) => vanishes(a, d_a) && vanishes(b, d_b),
+        };
+        constant.push(is_constant);
+
+        let mut nodes = self.nodes.borrow_mut();
+        nodes.push(node);
+        nodes.len() - 1
+    }
+
+    /// Call `f` with the gradient of the given node, reusing the previous backward sweep if
+    /// it was for the same node
+    fn with_gradient<R>(&self, output: usize, f: impl FnOnce(&[F]) -> R) -> R {
+        let mut cache = self.cache.borrow_mut();
+        if !matches!(*cache, Some((cached, _)) if cached == output) {
+            *cache = Some((output, self.backward(output)));
+        }
+        f(&cache.as_ref().unwrap().1)
+    }
+
+    /// Propagate the derivative of the given node back to every variable
+    fn backward(&self, output: usize) -> Vec<F> {
+        let nodes = self.nodes.borrow();
+        let mut adjoints = vec![F::zero(); output + 1];
+        let mut gradient = vec![F::zero(); self.num_variables];
+        adjoints[output] = F::one();
+
+        for (index, node) in nodes[..=output].iter().enumerate().rev() {
+            let adjoint = adjoints[index];
+            if adjoint.is_zero() {
+                continue;
+            }
+            match *node {
+                Node::Leaf(variable) => gradient[variable] = gradient[variable] + adjoint,
+                Node::Unary(parent, d) => adjoints[parent] = adjoints[parent] + adjoint * d,
+                Node::Binary(a, d_a, b, d_b) => {
+                    adjoints[a] = adjoints[a] + adjoint * d_a;
+                    adjoints[b] = adjoints[b] + adjoint * d_b;
+                }
+            }
+        }
+        gradient
+    }
+}
+
+impl<F: Num + Copy> TangentBuffer<F> for Tape<F> {
+    type Row = usize;
+
+    fn num_variables(&self) -> usize {
+        self.num_variables
+    }
+
+    fn seed(&self, index: usize) -> usize {
+        self.push(Node::Leaf(index))
+    }
+
+    fn scale(&self, row: usize, factor: F) -> usize {
+        self.push(Node::Unary(row, factor))
+    }
+
+    fn combine(&self, a: usize, b: usize, factor_a: F, factor_b: F) -> usize {
+        self.push(Node::Binary(a, factor_a, b, factor_b))
+    }
+
+    /// Performs a full backward pass unless the previous one was for the same row
+    fn derivative(&self, row: usize, index: usize) -> F {
+        self.with_gradient(row, |gradient| gradient[index])
+    }
+
+    fn gradient(&self, row: usize) -> Vec<F> {
+        self.with_gradient(row, |gradient| gradient.to_vec())
+    }
+
+    /// Answered from the recorded partial derivatives without a backward sweep, derivatives
+    /// which only cancel out numerically (like those of `x - x`) are not detected
+    fn is_constant(&self, row: usize) -> bool {
+        self.constant.borrow()[row]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::autograd::Tangents;
+
+    fn f<D: Float>(x: D, y: D, z: D) -> D {
+        let sigmoid = D::one() / (D::one() + (-x * y).exp());
+        sigmoid.tanh() * z.powf(y) / (x + z).ln() - x.sqrt().max(z)
+    }
+
+    #[test]
+    fn backward_matches_forward_mode() {
+        let tape = Tape::new(3);
+        let reverse = f(
+            tape.variable(0.4, 0),
+            tape.variable(1.5, 1),
+            tape.variable(2.5, 2),
+        );
+
+        let tangents = Tangents::new(3);
+        let forward = f(
+            tangents.variable(0.4, 0),
+            tangents.variable(1.5, 1),
+            tangents.variable(2.5, 2),
+        );
+
+        assert_eq!(reverse.val, forward.val);
+        for (a, b) in reverse.gradient().iter().zip(forward.gradient()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn reused_variables_accumulate_gradients() {
+        let tape = Tape::new(1);
+        let x = tape.variable(3., 0);
+        // d/dx (x * x + x) = 2x + 1
+        let y = x * x + x;
+        assert_eq!(y.gradient(), vec![7.]);
+        assert_eq!(tape.len(), 3);
+    }
+
+    #[test]
+    fn gradients_stay_valid_while_recording() {
+        let tape = Tape::new(2);
+        let x = tape.variable(3., 0);
+        let y = tape.variable(2., 1);
+        let z = x * y;
+        assert_eq!(z.derivative(0), 2.);
+
+        let w = z * y;
+        assert_eq!(w.gradient(), vec![4., 12.]);
+        assert_eq!(z.derivative(1), 3.);
+        assert_eq!(format!("{:?}", z), "DynDual { val: 6.0, e: [2.0, 3.0] }");
+    }
+
+    #[test]
+    fn zero_checks_use_the_recorded_operations() {
+        let tape = Tape::new(2);
+        let x = tape.variable(0., 0);
+        let y = tape.variable(2., 1);
+        assert!(!x.is_zero());
+        // d/dx x * x = 2x vanishes at zero
+        assert!((x * x).is_zero());
+        assert!(!(x * y).is_zero());
+        assert!((y * Var::constant(0.)).is_zero());
+        assert!((x * x + 1.).is_one());
+    }
+}
@@ -43,12 +43,12 @@ impl Dataset {
 
         // normalization temporarily turned off because debug
         Ok(Dataset {
-            train_test_split: train_test_split,
+            train_test_split,
             records: records / &record_means,
             labels: labels / &label_means,
-            record_means: record_means,
-            label_means: label_means,
-            batch_size: batch_size,
+            record_means,
+            label_means,
+            batch_size,
         })
     }
 
@@ -61,12 +61,12 @@ impl Dataset {
         batch_size: BatchSize,
     ) -> Result<Dataset> {
         Ok(Dataset {
-            train_test_split: train_test_split,
+            train_test_split,
             record_means: Array1::ones(records.ncols()),
             label_means: Array1::ones(labels.ncols()),
-            records: records,
-            labels: labels,
-            batch_size: batch_size,
+            records,
+            labels,
+            batch_size,
         })
     }
 
@@ -86,7 +86,7 @@ impl Dataset {
     }
 
     /// Return an iterator over training examples/labels in (sample, label) tupels
-    pub fn iter_train(&self) -> SampleIterator {
+    pub fn iter_train(&self) -> SampleIterator<'_> {
         let num_train = (self.records.nrows() as f64 * self.train_test_split) as usize;
 
         let batch_size = match self.batch_size {
@@ -97,15 +97,16 @@ impl Dataset {
 
         SampleIterator {
             index: 0,
-            num_batches: num_train.div_euclid(batch_size),
-            batch_size: batch_size,
-            samples: self.records.slice(s![..num_train, ..]).to_owned(),
-            labels: self.labels.slice(s![..num_train, ..]).to_owned(),
+            // there are no batches if there is no data
+            num_batches: num_train.checked_div(batch_size).unwrap_or(0),
+            batch_size,
+            samples: self.records.slice(s![..num_train, ..]),
+            labels: self.labels.slice(s![..num_train, ..]),
         }
     }
 
     /// Return an iterator over testing examples/labels in (sample, label) tupels
-    pub fn iter_test(&self) -> SampleIterator {
+    pub fn iter_test(&self) -> SampleIterator<'_> {
         let num_train = (self.records.nrows() as f64 * self.train_test_split) as usize;
         let num_test = self.records.nrows() - num_train;
 
@@ -117,26 +118,26 @@ impl Dataset {
 
         SampleIterator {
             index: 0,
-            num_batches: num_test.div_euclid(batch_size),
-            batch_size: batch_size,
-            samples: self.records.slice(s![num_train.., ..]).to_owned(),
-            labels: self.labels.slice(s![num_train.., ..]).to_owned(),
+            num_batches: num_test.checked_div(batch_size).unwrap_or(0),
+            batch_size,
+            samples: self.records.slice(s![num_train.., ..]),
+            labels: self.labels.slice(s![num_train.., ..]),
         }
     }
 }
 
-// BIG TODO: use lifetimes and array views here instead of cloning everything, this is slow!
+// TODO: yield array views instead of copying every batch
 /// An iterator over training/testing data. Yields (samples, labels) pairs where both
 /// samples and labels have the shape (num_fields x batch_size)
-pub struct SampleIterator {
+pub struct SampleIterator<'a> {
     index: usize,
     pub num_batches: usize,
     pub batch_size: usize,
-    samples: Array2<f64>,
-    labels: Array2<f64>,
+    samples: ArrayView2<'a, f64>,
+    labels: ArrayView2<'a, f64>,
 }
 
-impl Iterator for SampleIterator {
+impl Iterator for SampleIterator<'_> {
     type Item = (Array2<f64>, Array2<f64>);
     fn next(&mut self) -> Option<Self::Item> {
         if self.index >= self.num_batches {
@@ -5,6 +5,28 @@ use thiserror::Error;
 pub enum Error {
     #[error("Mismatched Dimensions: expected {expected:?}, found {found:?}")]
     MismatchedDimensions { expected: IxDyn, found: IxDyn },
+    #[error("Invalid axis {axis} for an array with {ndim} dimensions")]
+    InvalidAxis { axis: usize, ndim: usize },
     #[error("Expected some data but there is none")]
     NoData,
+    #[error(
+        "Skipped a step with {nan} NaN and {infinite} infinite values in the loss or its gradient"
+    )]
+    NonFiniteGradient { nan: usize, infinite: usize },
+    #[error("Invalid standard deviation {std_dev} of the gradient noise")]
+    InvalidNoise { std_dev: f64 },
+    #[error("Unsupported format version {found}, expected version {supported}")]
+    UnsupportedVersion { found: u32, supported: u32 },
+    #[error("Mismatched number type: expected {expected}, found {found}")]
+    MismatchedDtype { expected: String, found: String },
+    #[error("Mismatched parameter count: expected {expected}, found {found}")]
+    MismatchedParameterCount { expected: usize, found: usize },
+    #[error("Found {found} parameters but the network can only hold {capacity}")]
+    TooManyParameters { capacity: usize, found: usize },
+    #[error("Mismatched activation of layer {layer}: expected {expected}, found {found}")]
+    MismatchedActivation {
+        layer: usize,
+        expected: String,
+        found: String,
+    },
 }
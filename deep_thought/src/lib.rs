@@ -1,4 +1,4 @@
-#![feature(array_zip, negative_impls, auto_traits)]
+#![feature(negative_impls, auto_traits)]
 
 // //! This crate implements basic feedforward-neural Networks in rust.
 // //!
@@ -63,9 +63,16 @@ pub mod dataset;
 pub mod error;
 /// Loss functions
 pub mod loss;
+/// Metrics to evaluate networks
+pub mod metrics;
 /// Neural networks, Layers and math
 pub mod neural_network;
 /// Contains various different Types of optimizers
 pub mod optimizer;
 /// Common imports
 pub mod prelude;
+/// Saving and loading networks
+#[cfg(feature = "serde")]
+pub mod serialization;
+/// Training loops with callbacks
+pub mod trainer;
@@ -1,18 +1,320 @@
-use ndarray::prelude::*;
-use crate::autograd::Dual;
-use num_traits::Float;
+use crate::activation::{log_softmax, SoftmaxAxis};
+use crate::autograd::{Dual, DynDual, HyperDual, SparseTangents, Tangents, Var};
+use ndarray::{prelude::*, Zip};
+use num_traits::{Float, NumCast};
 
 /// A continuous, derivable function to describe how close one value is to another
-pub enum Loss {
+///
+/// Outputs and targets are laid out like the network's outputs, one sample per column.
+pub enum Loss<F> {
     /// Mean Squared Error Loss
     MSE,
+    /// Mean Absolute Error Loss
+    MAE,
+    /// Quadratic for errors up to `delta` and linear above, less sensitive to outliers than MSE
+    Huber(F),
+    /// Cross entropy of probabilities in `(0, 1)` (e.g. after a Sigmoid) against targets of 0 or 1
+    BinaryCrossEntropy,
+    /// Cross entropy of probability distributions (e.g. after a Softmax) against one-hot or
+    /// probability targets
+    CategoricalCrossEntropy,
+    /// Categorical cross entropy applied directly to unnormalized scores (logits).
+    ///
+    /// Fuses the softmax into the loss, which is more numerically stable than applying a Softmax
+    /// activation followed by [`Loss::CategoricalCrossEntropy`]. Use it with a Linear output layer.
+    SoftmaxCrossEntropy,
+    /// Negative log-likelihood of log-probabilities (e.g. after a LogSoftmax) against one-hot or
+    /// probability targets
+    NegativeLogLikelihood,
+    /// Hinge loss for targets of -1 or 1, as used by support vector machines
+    Hinge,
+    /// Kullback-Leibler divergence of the output distribution from the target distribution
+    KLDivergence,
 }
 
-impl Loss {
-    /// compute the loss for a given output/target pair
-    pub fn compute<F: Float, const N: usize>(&self, output: &Array2<Dual<F, N>>, target: &Array2<F>) -> Array2<Dual<F, N>> {
+/// How the per-element losses are combined into a single value.
+/// The unreduced losses are returned by [`LossOn::compute`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Reduction {
+    /// average over every element, or over every sample for losses comparing whole
+    /// distributions (see [`LossOn::per_sample`])
+    #[default]
+    Mean,
+    /// sum over every element
+    Sum,
+}
+
+/// A loss function comparing outputs of number type `D` with targets of type `F`.
+///
+/// Implement this generically over `D` to train with losses that are not part of [`Loss`]. Like
+/// activations, losses are written in terms of [`Float`] methods, so derivatives come for free
+/// and the loss works with every [`Engine`](crate::neural_network::Engine) (see [`LossFn`]).
+/// ```
+/// # use deep_thought::prelude::*;
+/// # use ndarray::prelude::*;
+/// # use num_traits::Float;
+/// /// Pinball loss for predicting the given quantile
+/// struct Quantile(f64);
+///
+/// impl<D: Float + From<f64>> LossOn<f64, D> for Quantile {
+///     fn compute(&self, output: &Array2<D>, target: &Array2<f64>) -> Array2<D> {
+///         let q: D = self.0.into();
+///         (&target.map(|&t| -> D { t.into() }) - output).map(|&e| (q * e).max((q - D::one()) * e))
+///     }
+/// }
+///
+/// let loss = Quantile(0.9).reduce(&array![[1., 3.]], &array![[2., 2.]], Reduction::Sum);
+/// assert!((loss - 1.).abs() < 1e-12);
+/// ```
+pub trait LossOn<F: Float, D: Float + From<F>> {
+    /// compute the loss for every element of a given output/target pair
+    fn compute(&self, output: &Array2<D>, target: &Array2<F>) -> Array2<D>;
+
+    /// Whether the losses of every column add up to the loss of one sample, like for losses
+    /// comparing probability distributions. [`Reduction::Mean`] then averages over the samples
+    /// instead of over every element.
+    fn per_sample(&self) -> bool {
+        false
+    }
+
+    /// compute the loss for a given output/target pair and combine it into a single value,
+    /// like the scalar loss passed to [`Optimizer::step`](crate::optimizer::Optimizer::step)
+    fn reduce(&self, output: &Array2<D>, target: &Array2<F>, reduction: Reduction) -> D {
+        let losses = self.compute(output, target);
+        let count = if self.per_sample() {
+            losses.ncols()
+        } else {
+            losses.len()
+        };
+        match reduction {
+            Reduction::Mean => losses.sum() / <D as NumCast>::from(count).unwrap(),
+            Reduction::Sum => losses.sum(),
+        }
+    }
+}
+
+impl<F: Float, D: Float + From<F>, L: LossOn<F, D> + ?Sized> LossOn<F, D> for Box<L> {
+    fn compute(&self, output: &Array2<D>, target: &Array2<F>) -> Array2<D> {
+        (**self).compute(output, target)
+    }
+
+    fn per_sample(&self) -> bool {
+        (**self).per_sample()
+    }
+
+    fn reduce(&self, output: &Array2<D>, target: &Array2<F>, reduction: Reduction) -> D {
+        (**self).reduce(output, target, reduction)
+    }
+}
+
+/// A loss function for networks tracking up to `N` parameters, which works with plain floats
+/// and the number type of every [`Engine`](crate::neural_network::Engine).
+///
+/// Implemented for every type implementing [`LossOn`] for all of these number types, which is
+/// the case for [`Loss`] and every [`LossOn`] implemented generically. `N` is only needed to name
+/// [`Dual<F, N>`] and [`HyperDual<F, N>`], so the trait can be used as a trait object, e.g. to
+/// pick losses at runtime:
+/// ```
+/// # use deep_thought::prelude::*;
+/// # use ndarray::prelude::*;
+/// let losses: Vec<(&str, Box<dyn LossFn<f64, 0>>)> = vec![
+///     ("mse", Box::new(Loss::MSE)),
+///     ("mae", Box::new(Loss::MAE)),
+/// ];
+/// for (_, loss) in &losses {
+///     assert_eq!(loss.reduce(&array![[1., -1.]], &array![[0., 0.]], Reduction::Mean), 1.);
+/// }
+/// ```
+pub trait LossFn<F: Float, const N: usize>:
+    LossOn<F, F>
+    + LossOn<F, Dual<F, N>>
+    + LossOn<F, HyperDual<F, N>>
+    + for<'t> LossOn<F, DynDual<'t, F, Tangents<F>>>
+    + for<'t> LossOn<F, DynDual<'t, F, SparseTangents<F>>>
+    + for<'t> LossOn<F, Var<'t, F>>
+{
+}
+
+impl<F: Float, const N: usize, L: ?Sized> LossFn<F, N> for L where
+    L: LossOn<F, F>
+        + LossOn<F, Dual<F, N>>
+        + LossOn<F, HyperDual<F, N>>
+        + for<'t> LossOn<F, DynDual<'t, F, Tangents<F>>>
+        + for<'t> LossOn<F, DynDual<'t, F, SparseTangents<F>>>
+        + for<'t> LossOn<F, Var<'t, F>>
+{
+}
+
+impl<F: Float, D: Float + From<F>> LossOn<F, D> for Loss<F> {
+    fn per_sample(&self) -> bool {
+        matches!(
+            self,
+            Loss::CategoricalCrossEntropy
+                | Loss::SoftmaxCrossEntropy
+                | Loss::NegativeLogLikelihood
+                | Loss::KLDivergence
+        )
+    }
+
+    fn compute(&self, output: &Array2<D>, target: &Array2<F>) -> Array2<D> {
+        // keep probabilities away from 0 and 1 so their logarithm stays finite
+        let eps: D = <D as NumCast>::from(1e-7).unwrap();
+        let clamp = |p: D| p.max(eps).min(D::one() - eps);
+        let target = target.map(|&t| -> D { t.into() });
+
         match &self {
-            Loss::MSE => (output - target) * (output - target),
+            Loss::MSE => (output - &target).map(|&x| x * x),
+            Loss::MAE => (output - &target).map(|&x| x.abs()),
+            Loss::Huber(delta) => {
+                let delta: D = (*delta).into();
+                let half = <D as NumCast>::from(0.5).unwrap();
+                (output - &target).map(|&x| {
+                    if x.abs() <= delta {
+                        half * x * x
+                    } else {
+                        delta * (x.abs() - half * delta)
+                    }
+                })
+            }
+            Loss::BinaryCrossEntropy => Zip::from(output).and(&target).map_collect(|&p, &t| {
+                let p = clamp(p);
+                -(t * p.ln() + (D::one() - t) * (D::one() - p).ln())
+            }),
+            Loss::CategoricalCrossEntropy => Zip::from(output)
+                .and(&target)
+                .map_collect(|&p, &t| -t * clamp(p).ln()),
+            Loss::SoftmaxCrossEntropy => Zip::from(&log_softmax(output, SoftmaxAxis::Features))
+                .and(&target)
+                .map_collect(|&log_p, &t| -t * log_p),
+            Loss::NegativeLogLikelihood => Zip::from(output)
+                .and(&target)
+                .map_collect(|&log_p, &t| -t * log_p),
+            Loss::Hinge => Zip::from(output)
+                .and(&target)
+                .map_collect(|&y, &t| (D::one() - t * y).max(D::zero())),
+            Loss::KLDivergence => Zip::from(output).and(&target).map_collect(|&p, &t| {
+                // 0 * ln(0) is defined as 0
+                if t > D::zero() {
+                    t * (t.ln() - clamp(p).ln())
+                } else {
+                    D::zero()
+                }
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activation::{Activation, ActivationOn};
+
+    fn assert_close(found: &Array2<f64>, expected: &Array2<f64>) {
+        assert!(
+            found.abs_diff_eq(expected, 1e-6),
+            "{} != {}",
+            found,
+            expected
+        );
+    }
+
+    #[test]
+    fn regression_losses() {
+        let output = array![[0.5, 3.], [-1., 2.]];
+        let target = array![[1., 0.], [-1., 1.5]];
+        assert_close(
+            &Loss::MSE.compute(&output, &target),
+            &array![[0.25, 9.], [0., 0.25]],
+        );
+        assert_close(
+            &Loss::MAE.compute(&output, &target),
+            &array![[0.5, 3.], [0., 0.5]],
+        );
+        assert_close(
+            &Loss::Huber(1.).compute(&output, &target),
+            &array![[0.125, 2.5], [0., 0.125]],
+        );
+    }
+
+    #[test]
+    fn classification_losses() {
+        let output = array![[0.8, 0.4], [0.2, 0.6]];
+        let target = array![[1., 0.], [0., 1.]];
+        let ln = |x: f64| x.ln();
+        assert_close(
+            &Loss::BinaryCrossEntropy.compute(&output, &target),
+            &array![[-ln(0.8), -ln(0.6)], [-ln(0.8), -ln(0.6)]],
+        );
+        assert_close(
+            &Loss::CategoricalCrossEntropy.compute(&output, &target),
+            &array![[-ln(0.8), 0.], [0., -ln(0.6)]],
+        );
+        assert_close(
+            &Loss::KLDivergence.compute(&output, &target),
+            &array![[-ln(0.8), 0.], [0., -ln(0.6)]],
+        );
+        assert_close(
+            &Loss::Hinge.compute(&array![[0.5, -2.]], &array![[1., 1.]]),
+            &array![[0.5, 3.]],
+        );
+    }
+
+    #[test]
+    fn fused_softmax_matches_softmax_activation() {
+        let logits = array![[1.], [3.], [-1.]];
+        let target = array![[0.2], [0.3], [0.5]];
+        let probs = Activation::<f64, 0>::Softmax {
+            axis: SoftmaxAxis::Features,
         }
+        .compute(&logits);
+        let expected = Loss::CategoricalCrossEntropy.compute(&probs, &target);
+        let log_probs = Activation::<f64, 0>::LogSoftmax {
+            axis: SoftmaxAxis::Features,
+        }
+        .compute(&logits);
+        assert_close(
+            &Loss::NegativeLogLikelihood.compute(&log_probs, &target),
+            &expected,
+        );
+        assert_close(
+            &Loss::SoftmaxCrossEntropy.compute(&logits, &target),
+            &expected,
+        );
+
+        // large logits would overflow a plain softmax
+        let large = Loss::SoftmaxCrossEntropy.compute(&array![[1000.], [0.]], &array![[0.], [1.]]);
+        assert_close(&large, &array![[0.], [1000.]]);
+    }
+
+    #[test]
+    fn reductions() {
+        let output = array![[1., 2.], [3., 4.]].map(|&x| Dual::<f64, 1>::variable(x, 0));
+        let target = Array2::<f64>::zeros((2, 2));
+        let mean = Loss::MSE.reduce(&output, &target, Reduction::Mean);
+        let sum = Loss::MSE.reduce(&output, &target, Reduction::Sum);
+        assert_eq!((mean.val, mean.e), (7.5, [5.]));
+        assert_eq!((sum.val, sum.e), (30., [20.]));
+    }
+
+    #[test]
+    fn distribution_losses_average_over_samples() {
+        let probs = array![[0.7], [0.2], [0.1]];
+        let target = array![[1.], [0.], [0.]];
+        let mean =
+            |loss: Loss<f64>, output: &Array2<f64>| loss.reduce(output, &target, Reduction::Mean);
+        let expected = -(0.7f64.ln());
+        assert!((mean(Loss::CategoricalCrossEntropy, &probs) - expected).abs() < 1e-12);
+        assert!((mean(Loss::NegativeLogLikelihood, &probs.mapv(f64::ln)) - expected).abs() < 1e-12);
+        assert!((mean(Loss::KLDivergence, &probs) - expected).abs() < 1e-12);
+        assert!((mean(Loss::SoftmaxCrossEntropy, &probs.mapv(f64::ln)) - expected).abs() < 1e-12);
+
+        // two samples average their losses
+        let probs = array![[0.7, 0.4], [0.3, 0.6]];
+        let target = array![[1., 0.], [0., 1.]];
+        let loss = Loss::CategoricalCrossEntropy.reduce(&probs, &target, Reduction::Mean);
+        assert!((loss + (0.7f64.ln() + 0.6f64.ln()) / 2.).abs() < 1e-12);
+        // element-wise losses still average over every element
+        let mse = Loss::MSE.reduce(&probs, &target, Reduction::Mean);
+        assert!((mse - 0.125).abs() < 1e-12);
     }
 }
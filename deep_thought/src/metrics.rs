@@ -0,0 +1,788 @@
+//! Classification and regression metrics
+//!
+//! Metrics accumulate statistics over any number of batches, so they can be computed over a whole
+//! dataset without keeping every output in memory. Outputs and targets are laid out like the
+//! network's outputs, one sample per column, which is how [`SampleIterator`](crate::dataset::SampleIterator)
+//! yields its labels.
+//!
+//! Every metric works with the number type of the network, like `f32` or `f64`. A metric is zero
+//! wherever it is undefined, e.g. before the first batch or for the precision of a class that is
+//! never predicted.
+//! ```
+//! # use deep_thought::prelude::*;
+//! # use deep_thought::metrics::*;
+//! # use ndarray::prelude::*;
+//! let net = NeuralNetwork::<f64, 6>::new().add_layer(Layer::new(1, 3));
+//! let dataset = Dataset::raw(array![[0.], [1.], [2.]], Array2::eye(3), 1., BatchSize::One).unwrap();
+//!
+//! let mut accuracy = Accuracy::new();
+//! for (samples, labels) in dataset.iter_train() {
+//!     let out = net.forward(&samples.map(|&x| Dual::constant(x)));
+//!     accuracy.update(&values(&out), &labels);
+//! }
+//! assert!(accuracy.result() <= 1.);
+//! ```
+
+use crate::autograd::Dual;
+use ndarray::{prelude::*, Zip};
+use num_traits::Float;
+
+/// A quantity that is accumulated over batches of outputs and targets
+pub trait Metric<F> {
+    /// Add a batch of outputs and targets
+    fn update(&mut self, output: &Array2<F>, target: &Array2<F>);
+
+    /// Value of the metric over all batches added since the last reset
+    fn result(&self) -> F;
+
+    /// Forget every batch
+    fn reset(&mut self);
+}
+
+/// The values of a batch of dual numbers, e.g. the output of [`NeuralNetwork::forward`](crate::neural_network::NeuralNetwork::forward)
+pub fn values<F: Copy, const N: usize>(output: &Array2<Dual<F, N>>) -> Array2<F> {
+    output.map(|x| x.val)
+}
+
+/// Predicted class of every sample: the row with the largest value, or for a single row
+/// whether the value reaches `threshold`
+fn classes<F: Float>(batch: &Array2<F>, threshold: F) -> Vec<usize> {
+    batch
+        .columns()
+        .into_iter()
+        .map(|column| {
+            if column.len() == 1 {
+                (column[0] >= threshold) as usize
+            } else {
+                argmax(column)
+            }
+        })
+        .collect()
+}
+
+fn argmax<F: Float>(column: ArrayView1<F>) -> usize {
+    column
+        .iter()
+        .enumerate()
+        .fold((0, F::neg_infinity()), |(best, max), (i, &x)| {
+            if x > max {
+                (i, x)
+            } else {
+                (best, max)
+            }
+        })
+        .0
+}
+
+/// `sum / count`, or zero if `count` is zero
+fn mean<F: Float>(sum: F, count: usize) -> F {
+    if count == 0 {
+        F::zero()
+    } else {
+        sum / F::from(count).unwrap()
+    }
+}
+
+/// `num / den`, or zero if `den` is zero
+fn ratio<F: Float>(num: usize, den: usize) -> F {
+    mean(F::from(num).unwrap(), den)
+}
+
+/// Convert a constant into the metric's number type
+fn constant<F: Float>(x: f64) -> F {
+    F::from(x).unwrap()
+}
+
+/// Fraction of samples whose predicted class matches the target class.
+///
+/// With multiple outputs the class is the index of the largest output (targets are one-hot),
+/// with a single output the class is whether the output reaches a threshold (targets are 0 or 1).
+#[derive(Debug, Clone)]
+pub struct Accuracy<F> {
+    threshold: F,
+    correct: usize,
+    total: usize,
+}
+
+impl<F: Float> Accuracy<F> {
+    /// Create an accuracy with a threshold of 0.5
+    pub fn new() -> Self {
+        Accuracy {
+            threshold: constant(0.5),
+            correct: 0,
+            total: 0,
+        }
+    }
+
+    /// Set the threshold of single output classifiers
+    pub fn threshold(mut self, threshold: F) -> Self {
+        self.threshold = threshold;
+        self
+    }
+}
+
+impl<F: Float> Default for Accuracy<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Float> Metric<F> for Accuracy<F> {
+    fn update(&mut self, output: &Array2<F>, target: &Array2<F>) {
+        let predicted = classes(output, self.threshold);
+        let actual = classes(target, constant(0.5));
+        self.correct += predicted
+            .iter()
+            .zip(&actual)
+            .filter(|(p, a)| p == a)
+            .count();
+        self.total += actual.len();
+    }
+
+    fn result(&self) -> F {
+        ratio(self.correct, self.total)
+    }
+
+    fn reset(&mut self) {
+        self.correct = 0;
+        self.total = 0;
+    }
+}
+
+/// Fraction of samples whose target class is among the `k` largest outputs
+#[derive(Debug, Clone)]
+pub struct TopKAccuracy {
+    k: usize,
+    correct: usize,
+    total: usize,
+}
+
+impl TopKAccuracy {
+    /// Create a top-k accuracy
+    pub fn new(k: usize) -> Self {
+        TopKAccuracy {
+            k,
+            correct: 0,
+            total: 0,
+        }
+    }
+}
+
+impl<F: Float> Metric<F> for TopKAccuracy {
+    fn update(&mut self, output: &Array2<F>, target: &Array2<F>) {
+        for (out, target) in output.columns().into_iter().zip(target.columns()) {
+            let score = out[argmax(target)];
+            let rank = out.iter().filter(|&&x| x > score).count();
+            if rank < self.k {
+                self.correct += 1;
+            }
+            self.total += 1;
+        }
+    }
+
+    fn result(&self) -> F {
+        ratio(self.correct, self.total)
+    }
+
+    fn reset(&mut self) {
+        self.correct = 0;
+        self.total = 0;
+    }
+}
+
+/// How per-class scores are combined into a single value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Average {
+    /// Unweighted mean of the scores of every class
+    Macro,
+    /// Score of the true/false positives/negatives summed over every class
+    Micro,
+    /// Score of class 1 only, the positive class of single output classifiers.
+    /// Requires at least 2 classes
+    Binary,
+}
+
+/// Counts how often every class is predicted as every other class.
+///
+/// Classes are determined like for [`Accuracy`].
+#[derive(Debug, Clone)]
+pub struct ConfusionMatrix<F> {
+    threshold: F,
+    /// entry `[actual, predicted]` counts the samples of class `actual` that were predicted as `predicted`
+    matrix: Array2<usize>,
+}
+
+impl<F: Float> ConfusionMatrix<F> {
+    /// Create a confusion matrix for `num_classes` classes, which is 2 for single output classifiers
+    pub fn new(num_classes: usize) -> Self {
+        ConfusionMatrix {
+            threshold: constant(0.5),
+            matrix: Array2::zeros((num_classes, num_classes)),
+        }
+    }
+
+    /// Set the threshold of single output classifiers
+    pub fn threshold(mut self, threshold: F) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Add a batch of outputs and targets
+    ///
+    /// # Panics
+    ///
+    /// Panics if a class does not fit into the matrix
+    pub fn update(&mut self, output: &Array2<F>, target: &Array2<F>) {
+        let predicted = classes(output, self.threshold);
+        let actual = classes(target, constant(0.5));
+        let num_classes = self.matrix.nrows();
+        for (&p, &a) in predicted.iter().zip(&actual) {
+            assert!(
+                p < num_classes && a < num_classes,
+                "found class {} but the confusion matrix only has {} classes",
+                p.max(a),
+                num_classes
+            );
+            self.matrix[[a, p]] += 1;
+        }
+    }
+
+    /// Forget every batch
+    pub fn reset(&mut self) {
+        self.matrix.fill(0);
+    }
+
+    /// The counts, where entry `[actual, predicted]` counts the samples of class `actual`
+    /// that were predicted as `predicted`
+    pub fn matrix(&self) -> &Array2<usize> {
+        &self.matrix
+    }
+
+    /// Fraction of predictions of a class that are correct
+    pub fn precision(&self, average: Average) -> F {
+        self.average(average, |tp, predicted, _| ratio(tp, predicted))
+    }
+
+    /// Fraction of the samples of a class that are predicted correctly
+    pub fn recall(&self, average: Average) -> F {
+        self.average(average, |tp, _, actual| ratio(tp, actual))
+    }
+
+    /// Harmonic mean of precision and recall
+    pub fn f1(&self, average: Average) -> F {
+        self.average(average, |tp, predicted, actual| {
+            ratio(2 * tp, predicted + actual)
+        })
+    }
+
+    /// Combine the score of every class, which is computed from its true positives,
+    /// number of predictions and number of samples
+    ///
+    /// # Panics
+    ///
+    /// Panics if `average` is [`Average::Binary`] but there are less than 2 classes
+    fn average<G: Fn(usize, usize, usize) -> F>(&self, average: Average, score: G) -> F {
+        let counts = |class: usize| {
+            (
+                self.matrix[[class, class]],
+                self.matrix.column(class).sum(),
+                self.matrix.row(class).sum(),
+            )
+        };
+        let num_classes = self.matrix.nrows();
+        match average {
+            Average::Macro => {
+                let total = (0..num_classes).fold(F::zero(), |acc, class| {
+                    let (tp, predicted, actual) = counts(class);
+                    acc + score(tp, predicted, actual)
+                });
+                mean(total, num_classes)
+            }
+            Average::Micro => {
+                let tp = self.matrix.diag().sum();
+                let total = self.matrix.sum();
+                score(tp, total, total)
+            }
+            Average::Binary => {
+                assert_binary(num_classes);
+                let (tp, predicted, actual) = counts(1);
+                score(tp, predicted, actual)
+            }
+        }
+    }
+}
+
+/// Class 1 only exists with at least 2 classes
+fn assert_binary(num_classes: usize) {
+    assert!(
+        num_classes >= 2,
+        "binary averaging needs at least 2 classes, found {}",
+        num_classes
+    );
+}
+
+macro_rules! confusion_metric {
+    ($name: ident, $score: ident, $doc: expr) => {
+        #[doc = $doc]
+        #[derive(Debug, Clone)]
+        pub struct $name<F> {
+            confusion: ConfusionMatrix<F>,
+            average: Average,
+        }
+
+        impl<F: Float> $name<F> {
+            /// Create the metric for `num_classes` classes, which is 2 for single output classifiers
+            ///
+            /// # Panics
+            ///
+            /// Panics if `average` is [`Average::Binary`] but `num_classes` is less than 2
+            pub fn new(num_classes: usize, average: Average) -> Self {
+                if average == Average::Binary {
+                    assert_binary(num_classes);
+                }
+                $name {
+                    confusion: ConfusionMatrix::new(num_classes),
+                    average,
+                }
+            }
+
+            /// Set the threshold of single output classifiers
+            pub fn threshold(mut self, threshold: F) -> Self {
+                self.confusion = self.confusion.threshold(threshold);
+                self
+            }
+        }
+
+        impl<F: Float> Metric<F> for $name<F> {
+            fn update(&mut self, output: &Array2<F>, target: &Array2<F>) {
+                self.confusion.update(output, target);
+            }
+
+            fn result(&self) -> F {
+                self.confusion.$score(self.average)
+            }
+
+            fn reset(&mut self) {
+                self.confusion.reset();
+            }
+        }
+    };
+}
+
+confusion_metric!(
+    Precision,
+    precision,
+    "Fraction of predictions of a class that are correct, see [`ConfusionMatrix::precision`]"
+);
+confusion_metric!(
+    Recall,
+    recall,
+    "Fraction of the samples of a class that are predicted correctly, see [`ConfusionMatrix::recall`]"
+);
+confusion_metric!(
+    F1,
+    f1,
+    "Harmonic mean of precision and recall, see [`ConfusionMatrix::f1`]"
+);
+
+/// Area under the receiver operating characteristic curve, which is the probability that a random
+/// positive sample is scored higher than a random negative one.
+///
+/// Every output is treated as the score of an independent binary decision whose target is 0 or 1.
+/// With multiple outputs, the decisions of all outputs are ranked together, which is the
+/// micro-average over the outputs. The AUC is zero if all targets are of the same class, since
+/// there are no pairs of positive and negative samples to compare.
+#[derive(Debug, Clone, Default)]
+pub struct AUC<F> {
+    /// score and whether the target is positive, for every output
+    scores: Vec<(F, bool)>,
+}
+
+impl<F: Float> AUC<F> {
+    /// Create an empty AUC
+    pub fn new() -> Self {
+        AUC { scores: vec![] }
+    }
+}
+
+impl<F: Float> Metric<F> for AUC<F> {
+    fn update(&mut self, output: &Array2<F>, target: &Array2<F>) {
+        let half = constant(0.5);
+        self.scores
+            .extend(output.iter().zip(target).map(|(&o, &t)| (o, t >= half)));
+    }
+
+    fn result(&self) -> F {
+        let mut scores = self.scores.clone();
+        scores.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        // sum the ranks of the positive samples, tied scores share their average rank
+        let mut positive_ranks = 0.;
+        let mut start = 0;
+        while start < scores.len() {
+            let end = start
+                + scores[start..]
+                    .iter()
+                    .take_while(|(score, _)| *score == scores[start].0)
+                    .count();
+            let rank = (start + end + 1) as f64 / 2.;
+            let positives = scores[start..end].iter().filter(|(_, p)| *p).count();
+            positive_ranks += rank * positives as f64;
+            start = end;
+        }
+
+        let positives = scores.iter().filter(|(_, p)| *p).count();
+        let negatives = scores.len() - positives;
+        let p = positives as f64;
+        constant(mean(
+            positive_ranks - p * (p + 1.) / 2.,
+            positives * negatives,
+        ))
+    }
+
+    fn reset(&mut self) {
+        self.scores.clear();
+    }
+}
+
+/// Mean absolute error over every output
+#[derive(Debug, Clone, Default)]
+pub struct MAE<F> {
+    sum: F,
+    count: usize,
+}
+
+impl<F: Float> MAE<F> {
+    /// Create an empty MAE
+    pub fn new() -> Self {
+        MAE {
+            sum: F::zero(),
+            count: 0,
+        }
+    }
+}
+
+impl<F: Float> Metric<F> for MAE<F> {
+    fn update(&mut self, output: &Array2<F>, target: &Array2<F>) {
+        self.sum = self.sum
+            + Zip::from(output)
+                .and(target)
+                .fold(F::zero(), |acc, &o, &t| acc + (o - t).abs());
+        self.count += output.len();
+    }
+
+    fn result(&self) -> F {
+        mean(self.sum, self.count)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// Root mean squared error over every output
+#[derive(Debug, Clone, Default)]
+pub struct RMSE<F> {
+    sum: F,
+    count: usize,
+}
+
+impl<F: Float> RMSE<F> {
+    /// Create an empty RMSE
+    pub fn new() -> Self {
+        RMSE {
+            sum: F::zero(),
+            count: 0,
+        }
+    }
+}
+
+impl<F: Float> Metric<F> for RMSE<F> {
+    fn update(&mut self, output: &Array2<F>, target: &Array2<F>) {
+        self.sum = self.sum
+            + Zip::from(output)
+                .and(target)
+                .fold(F::zero(), |acc, &o, &t| acc + (o - t) * (o - t));
+        self.count += output.len();
+    }
+
+    fn result(&self) -> F {
+        mean(self.sum, self.count).sqrt()
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// Coefficient of determination, the fraction of the targets' variance that is explained
+/// by the outputs. With multiple outputs, the variances of every output are summed.
+/// The R² is zero if the targets do not vary.
+#[derive(Debug, Clone, Default)]
+pub struct R2<F> {
+    count: usize,
+    /// mean of the targets of every output
+    mean: Array1<F>,
+    /// sum of the squared deviations of the targets from their mean, for every output
+    m2: Array1<F>,
+    /// sum of the squared errors of every output
+    sum_sq_error: Array1<F>,
+}
+
+impl<F: Float> R2<F> {
+    /// Create an empty R²
+    pub fn new() -> Self {
+        R2 {
+            count: 0,
+            mean: Array1::zeros(0),
+            m2: Array1::zeros(0),
+            sum_sq_error: Array1::zeros(0),
+        }
+    }
+}
+
+impl<F: Float> Metric<F> for R2<F> {
+    fn update(&mut self, output: &Array2<F>, target: &Array2<F>) {
+        // The number of outputs might only be known once the first batch arrives
+        if self.count == 0 {
+            self.mean = Array1::zeros(target.nrows());
+            self.m2 = Array1::zeros(target.nrows());
+            self.sum_sq_error = Array1::zeros(target.nrows());
+        }
+        if target.ncols() == 0 {
+            return;
+        }
+        self.sum_sq_error =
+            &self.sum_sq_error + &(output - target).mapv(|e| e * e).sum_axis(Axis(1));
+
+        // Merge the mean and squared deviations of the batch with the previous ones (Chan et al.),
+        // which does not cancel like the difference of the sums of squares and squared sum
+        let (n_a, n_b) = (self.count, target.ncols());
+        let n = F::from(n_a + n_b).unwrap();
+        let (n_a, n_b) = (F::from(n_a).unwrap(), F::from(n_b).unwrap());
+        let batch_mean = target.sum_axis(Axis(1)).mapv(|s| s / n_b);
+        let batch_m2 = (target - &batch_mean.view().insert_axis(Axis(1)))
+            .mapv(|d| d * d)
+            .sum_axis(Axis(1));
+        let delta = &batch_mean - &self.mean;
+        self.mean = &self.mean + &delta.mapv(|d| d * n_b / n);
+        self.m2 = &self.m2 + &batch_m2 + &delta.mapv(|d| d * d * n_a * n_b / n);
+        self.count += target.ncols();
+    }
+
+    fn result(&self) -> F {
+        let total = self.m2.sum();
+        if total > F::zero() {
+            F::one() - self.sum_sq_error.sum() / total
+        } else {
+            F::zero()
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed every batch into `metric` and return its result
+    fn accumulate<M: Metric<f64>>(mut metric: M, batches: &[(Array2<f64>, Array2<f64>)]) -> f64 {
+        for (output, target) in batches {
+            metric.update(output, target);
+        }
+        metric.result()
+    }
+
+    fn assert_close(found: f64, expected: f64) {
+        assert!(
+            (found - expected).abs() < 1e-12,
+            "{} != {}",
+            found,
+            expected
+        );
+    }
+
+    /// 6 samples of 3 classes, split into two batches.
+    /// Actual classes are 0 0 1 1 2 2, predicted classes are 0 1 1 1 2 0
+    fn multiclass() -> Vec<(Array2<f64>, Array2<f64>)> {
+        let output = array![
+            [0.7, 0.2, 0.1, 0.2, 0.1, 0.5],
+            [0.2, 0.5, 0.8, 0.5, 0.3, 0.1],
+            [0.1, 0.3, 0.1, 0.3, 0.6, 0.4]
+        ];
+        let target = array![
+            [1., 1., 0., 0., 0., 0.],
+            [0., 0., 1., 1., 0., 0.],
+            [0., 0., 0., 0., 1., 1.]
+        ];
+        vec![
+            (
+                output.slice(s![.., ..4]).to_owned(),
+                target.slice(s![.., ..4]).to_owned(),
+            ),
+            (
+                output.slice(s![.., 4..]).to_owned(),
+                target.slice(s![.., 4..]).to_owned(),
+            ),
+        ]
+    }
+
+    #[test]
+    fn accuracy() {
+        assert_close(accumulate(Accuracy::new(), &multiclass()), 4. / 6.);
+        assert_close(accumulate(TopKAccuracy::new(1), &multiclass()), 4. / 6.);
+        // the 6th sample has its target as second largest output, the 2nd sample as smallest
+        assert_close(accumulate(TopKAccuracy::new(2), &multiclass()), 5. / 6.);
+        assert_close(accumulate(TopKAccuracy::new(3), &multiclass()), 1.);
+
+        let binary = [(array![[0.2, 0.6, 0.9, 0.4]], array![[0., 1., 0., 0.]])];
+        assert_close(accumulate(Accuracy::new(), &binary), 3. / 4.);
+        assert_close(accumulate(Accuracy::new().threshold(0.3), &binary), 2. / 4.);
+    }
+
+    #[test]
+    fn confusion_matrix() {
+        let mut confusion = ConfusionMatrix::new(3);
+        for (output, target) in multiclass() {
+            confusion.update(&output, &target);
+        }
+        assert_eq!(confusion.matrix(), array![[1, 1, 0], [0, 2, 0], [1, 0, 1]]);
+
+        // per class precision is 1/2, 2/3, 1 and recall is 1/2, 1, 1/2
+        let precision = (1. / 2. + 2. / 3. + 1.) / 3.;
+        let recall = (1. / 2. + 1. + 1. / 2.) / 3.;
+        let f1 = (1. / 2. + 4. / 5. + 2. / 3.) / 3.;
+        assert_close(confusion.precision(Average::Macro), precision);
+        assert_close(confusion.recall(Average::Macro), recall);
+        assert_close(confusion.f1(Average::Macro), f1);
+        assert_close(confusion.precision(Average::Micro), 4. / 6.);
+        assert_close(confusion.recall(Average::Micro), 4. / 6.);
+        assert_close(confusion.f1(Average::Micro), 4. / 6.);
+        assert_close(confusion.precision(Average::Binary), 2. / 3.);
+
+        assert_close(
+            accumulate(Precision::new(3, Average::Macro), &multiclass()),
+            precision,
+        );
+        assert_close(
+            accumulate(Recall::new(3, Average::Macro), &multiclass()),
+            recall,
+        );
+        assert_close(accumulate(F1::new(3, Average::Macro), &multiclass()), f1);
+
+        confusion.reset();
+        assert_eq!(confusion.matrix().sum(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "found class 2 but the confusion matrix only has 2 classes")]
+    fn confusion_matrix_too_small() {
+        ConfusionMatrix::new(2).update(&array![[0.], [0.], [1.]], &array![[1.], [0.], [0.]]);
+    }
+
+    #[test]
+    fn auc() {
+        let batches = [
+            (array![[0.1, 0.4]], array![[0., 0.]]),
+            (array![[0.35, 0.8]], array![[1., 1.]]),
+        ];
+        assert_close(accumulate(AUC::new(), &batches), 0.75);
+
+        // ties count half
+        let tied = [(array![[0.5, 0.5, 0.5, 0.9]], array![[0., 1., 0., 1.]])];
+        assert_close(accumulate(AUC::new(), &tied), 0.75);
+
+        // multiple outputs are ranked together
+        let rows = [(array![[0.1, 0.4], [0.35, 0.8]], array![[0., 0.], [1., 1.]])];
+        assert_close(accumulate(AUC::new(), &rows), 0.75);
+
+        // undefined without both classes
+        let positive = [(array![[0.1, 0.4]], array![[1., 1.]])];
+        assert_eq!(accumulate(AUC::new(), &positive), 0.);
+    }
+
+    #[test]
+    fn undefined_metrics_are_zero() {
+        assert_eq!(accumulate(Accuracy::new(), &[]), 0.);
+        assert_eq!(accumulate(TopKAccuracy::new(2), &[]), 0.);
+        assert_eq!(accumulate(Precision::new(3, Average::Macro), &[]), 0.);
+        assert_eq!(accumulate(AUC::new(), &[]), 0.);
+        assert_eq!(accumulate(MAE::new(), &[]), 0.);
+        assert_eq!(accumulate(RMSE::new(), &[]), 0.);
+        assert_eq!(accumulate(R2::new(), &[]), 0.);
+
+        // targets without variance
+        let constant = [(array![[1., 2.]], array![[1., 1.]])];
+        assert_eq!(accumulate(R2::new(), &constant), 0.);
+    }
+
+    #[test]
+    fn single_precision() {
+        let (output, target) = (
+            array![[0.2f32, 0.9], [0.8, 0.4]],
+            array![[0., 1.], [1., 0.]],
+        );
+        let mut accuracy = Accuracy::new();
+        accuracy.update(&output, &target);
+        assert_eq!(accuracy.result(), 1f32);
+
+        let mut mae = MAE::new();
+        mae.update(&output, &target);
+        assert!((mae.result() - 0.225f32).abs() < 1e-6);
+    }
+
+    #[test]
+    fn regression_metrics() {
+        let batches = [
+            (array![[2.5, 0.], [1., 1.]], array![[3., -0.5], [1., 2.]]),
+            (array![[2.], [2.]], array![[2.], [4.]]),
+        ];
+        assert_close(accumulate(MAE::new(), &batches), 4. / 6.);
+        assert_close(accumulate(RMSE::new(), &batches), (5.5f64 / 6.).sqrt());
+
+        // variance of the first output: mean 1.5, squared deviations 2.25 + 4 + 0.25
+        // variance of the second output: mean 7/3, squared deviations 16/9 + 1/9 + 25/9
+        let total = 6.5 + 42. / 9.;
+        assert_close(accumulate(R2::new(), &batches), 1. - 5.5 / total);
+
+        let mut mae = MAE::new();
+        mae.update(&batches[0].0, &batches[0].1);
+        mae.reset();
+        mae.update(&batches[1].0, &batches[1].1);
+        assert_close(mae.result(), 1.);
+    }
+
+    #[test]
+    fn r2_of_large_targets() {
+        // the variance of the targets is tiny compared to their squares
+        let target = array![[1e9, 1e9 + 1., 1e9 + 2., 1e9 + 3.]];
+        let output = &target + 0.5;
+        let batches = [
+            (
+                output.slice(s![.., ..1]).to_owned(),
+                target.slice(s![.., ..1]).to_owned(),
+            ),
+            (
+                output.slice(s![.., 1..]).to_owned(),
+                target.slice(s![.., 1..]).to_owned(),
+            ),
+        ];
+        // squared deviations from the mean 1e9 + 1.5 sum to 5
+        assert_close(accumulate(R2::new(), &batches), 1. - 1. / 5.);
+    }
+
+    #[test]
+    #[should_panic(expected = "binary averaging needs at least 2 classes, found 1")]
+    fn binary_average_needs_two_classes() {
+        Precision::<f64>::new(1, Average::Binary);
+    }
+
+    #[test]
+    #[should_panic(expected = "binary averaging needs at least 2 classes, found 1")]
+    fn binary_confusion_matrix_needs_two_classes() {
+        ConfusionMatrix::<f64>::new(1).recall(Average::Binary);
+    }
+}
@@ -1,33 +1,77 @@
 use crate::{
-    activation::Activation,
-    autograd::{Dual, DualDistribution},
-    error::Error,
-    loss::Loss,
+    activation::{Activation, ActivationFn, ActivationOn},
+    autograd::{Dual, DynDual, SparseTangents, TangentBuffer, Tangents, Tape},
+    loss::{LossFn, Reduction},
 };
 use ndarray::prelude::*;
 use ndarray_rand::RandomExt;
-use num_traits::{Float, Num};
+use num_traits::Float;
 use rand_distr::{Distribution, Normal, StandardNormal};
 
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+/// Algorithm used to compute the derivatives of the loss with respect to the network's parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    /// Forward-mode using [`Dual`] numbers, requires the network to have at most `N` parameters
+    Forward,
+    /// Forward-mode using [`DynDual`] numbers with dense [`Tangents`]
+    Dynamic,
+    /// Forward-mode using [`DynDual`] numbers with [`SparseTangents`]
+    Sparse,
+    /// Reverse-mode using a [`Tape`], whose cost does not grow with the number of parameters
+    Reverse,
+}
+
 /// A Neural Network consisting of a an input/output and any number of additional hidden [`Layer`]s
+///
+/// Every layer uses an activation function of type `A`, which defaults to the built-in [`Activation`]s.
+///
+/// `N` is the number of parameters [`Dual`] numbers can track, which only matters for
+/// [`NeuralNetwork::forward`] and [`Engine::Forward`]. Networks whose size is only known at runtime
+/// keep the default and are trained with one of the other engines.
+///
+/// Forward passes only borrow the network immutably and networks are `Send + Sync` (given that
+/// `F` and `A` are), so a trained network can serve predictions from many threads at once.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub struct NeuralNetwork<F, const N: usize> {
-    pub layers: Vec<Layer<F, N>>,
+pub struct NeuralNetwork<F, const N: usize = 0, A = Activation<F, N>> {
+    pub layers: Vec<Layer<F, N, A>>,
+}
+
+/// The role a parameter plays within its [`Layer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterRole {
+    /// Entry of the weight matrix
+    Weight { row: usize, col: usize },
+    /// Entry of the bias vector
+    Bias { row: usize },
+}
+
+/// Describes where a parameter is located within a [`NeuralNetwork`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParameterInfo {
+    /// Global index of the parameter, which is also the index of its derivative within [`Dual::e`]
+    pub index: usize,
+    /// Index of the layer containing the parameter
+    pub layer: usize,
+    /// Whether the parameter is a weight or a bias
+    pub role: ParameterRole,
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[allow(non_snake_case)] // non snake case kinda makes sense with matrices
-/// A single neuron layer with an associated [`Activation`] function
-pub struct Layer<F, const N: usize> {
+/// A single neuron layer with an associated activation function, see [`ActivationFn`]
+pub struct Layer<F, const N: usize = 0, A = Activation<F, N>> {
     /// Weight matrix
     pub W: Array2<F>,
     /// Bias vector
     pub B: Array2<F>,
     /// Activation function to allow for nonlinear transformations
-    activation: Activation<F, N>,
+    pub(crate) activation: A,
 }
 
 impl<F: Float, const N: usize> Layer<F, N>
@@ -47,71 +91,798 @@ where
     }
 }
 
-impl<F: 'static + Float, const N: usize> Layer<F, N> {
-    /// define a activation function for that layer (default is f(x) = x )
-    pub fn activation(mut self, a: Activation<F, N>) -> Self {
-        self.activation = a;
-        self
+impl<F: 'static + Float, const N: usize, A> Layer<F, N, A> {
+    /// define a activation function for that layer (default is f(x) = x ).
+    ///
+    /// Accepts the built-in [`Activation`]s as well as any other [`ActivationFn`]
+    pub fn activation<T: ActivationFn<F, N>>(self, activation: T) -> Layer<F, N, T> {
+        Layer {
+            W: self.W,
+            B: self.B,
+            activation,
+        }
+    }
+
+    /// Number of trainable parameters (weights and biases) within the layer
+    pub fn num_parameters(&self) -> usize {
+        self.W.len() + self.B.len()
+    }
+
+    /// Get the layer's `index`-th parameter. Parameters are ordered by weights (row-major)
+    /// followed by biases.
+    pub fn parameter(&self, index: usize) -> Option<&F> {
+        let num_weights = self.W.len();
+        if index < num_weights {
+            let ncols = self.W.ncols();
+            self.W.get((index / ncols, index % ncols))
+        } else {
+            self.B.get((index - num_weights, 0))
+        }
+    }
+
+    /// Get a mutable reference to the layer's `index`-th parameter, see [`Layer::parameter`]
+    pub fn parameter_mut(&mut self, index: usize) -> Option<&mut F> {
+        let num_weights = self.W.len();
+        if index < num_weights {
+            let ncols = self.W.ncols();
+            self.W.get_mut((index / ncols, index % ncols))
+        } else {
+            self.B.get_mut((index - num_weights, 0))
+        }
+    }
+
+    /// Iterate over the layer's parameters in [`Layer::parameter`] order
+    pub fn parameters(&self) -> impl Iterator<Item = (ParameterRole, &F)> {
+        let weights = self
+            .W
+            .indexed_iter()
+            .map(|((row, col), w)| (ParameterRole::Weight { row, col }, w));
+        let biases = self
+            .B
+            .indexed_iter()
+            .map(|((row, _), b)| (ParameterRole::Bias { row }, b));
+        weights.chain(biases)
     }
-    /// forward-pass a batch of input vectors through the layer
-    pub fn forward(&mut self, inp: &Array2<Dual<F, N>>) -> Array2<Dual<F, N>> {
-        unimplemented!()
-        // let z = self.W.dot(inp) + &self.B;
-        // self.activation.compute(&z)
+
+    /// Mutably iterate over the layer's parameters in [`Layer::parameter`] order
+    pub fn parameters_mut(&mut self) -> impl Iterator<Item = (ParameterRole, &mut F)> {
+        let weights = self
+            .W
+            .indexed_iter_mut()
+            .map(|((row, col), w)| (ParameterRole::Weight { row, col }, w));
+        let biases = self
+            .B
+            .indexed_iter_mut()
+            .map(|((row, _), b)| (ParameterRole::Bias { row }, b));
+        weights.chain(biases)
+    }
+}
+
+impl<F: 'static + Float, const N: usize, A: ActivationFn<F, N>> Layer<F, N, A> {
+    /// forward-pass a batch of input vectors through the layer.
+    ///
+    /// `offset` is the global index of the layer's first parameter. Every parameter is seeded as
+    /// a dual variable whose index is `offset` plus its position in [`Layer::parameter`] order,
+    /// so the output carries the derivative with respect to each parameter.
+    pub fn forward(&self, inp: &Array2<Dual<F, N>>, offset: usize) -> Array2<Dual<F, N>> {
+        self.forward_with(inp, offset, Dual::variable)
+    }
+
+    /// forward-pass a batch of input vectors through the layer using any differentiable number type.
+    ///
+    /// Like [`Layer::forward`], except that every parameter is lifted by calling `seed` with its
+    /// value and global index.
+    pub fn forward_with<D, S>(&self, inp: &Array2<D>, offset: usize, seed: S) -> Array2<D>
+    where
+        D: Float + From<F>,
+        S: Fn(F, usize) -> D,
+        A: ActivationOn<F, D>,
+    {
+        let ncols = self.W.ncols();
+        let bias_offset = offset + self.W.len();
+
+        let w = Array2::from_shape_fn(self.W.dim(), |(row, col)| {
+            seed(self.W[[row, col]], offset + row * ncols + col)
+        });
+        let b = Array2::from_shape_fn(self.B.dim(), |(row, _)| {
+            seed(self.B[[row, 0]], bias_offset + row)
+        });
+
+        let z = dot(&w, inp) + &b;
+        self.activation.compute(&z)
+    }
+
+    /// forward-pass a batch of input vectors through the layer without tracking any derivatives
+    pub fn predict(&self, inp: &Array2<F>) -> Array2<F> {
+        let z = self.W.dot(inp) + &self.B;
+        self.activation.compute(&z)
     }
 }
 
-impl<F: 'static + Float, const N: usize> NeuralNetwork<F, N> {
+/// Matrix product for number types which do not implement [`ndarray::LinalgScalar`]
+fn dot<D: Float>(a: &Array2<D>, b: &Array2<D>) -> Array2<D> {
+    Array2::from_shape_fn((a.nrows(), b.ncols()), |(row, col)| {
+        a.row(row)
+            .iter()
+            .zip(b.column(col))
+            .fold(D::zero(), |acc, (&x, &y)| acc + x * y)
+    })
+}
+
+impl<F: 'static + Float, const N: usize, A> NeuralNetwork<F, N, A> {
     /// Initialize a empty Neural Network
-    pub fn new() -> NeuralNetwork<F, N> {
+    pub fn new() -> NeuralNetwork<F, N, A> {
         NeuralNetwork { layers: vec![] }
     }
 
     /// add a hidden layer to the network
-    pub fn add_layer(mut self, layer: Layer<F, N>) -> NeuralNetwork<F, N> {
+    pub fn add_layer(mut self, layer: Layer<F, N, A>) -> NeuralNetwork<F, N, A> {
         self.layers.push(layer);
         self
     }
 
+    /// Total number of trainable parameters across all layers
+    pub fn num_parameters(&self) -> usize {
+        self.layers.iter().map(|layer| layer.num_parameters()).sum()
+    }
+
+    /// Get the parameter with the given global index. The global index is the position of
+    /// a parameter when walking all layers in order, which is also the index of its derivative
+    /// within [`Dual::e`] (or [`DynDual::gradient`]) after a forward pass.
+    pub fn parameter(&self, index: usize) -> Option<&F> {
+        let (layer, local_index) = self.locate(index)?;
+        self.layers[layer].parameter(local_index)
+    }
+
+    /// Get a mutable reference to the parameter with the given global index, see [`NeuralNetwork::parameter`]
+    pub fn parameter_mut(&mut self, index: usize) -> Option<&mut F> {
+        let (layer, local_index) = self.locate(index)?;
+        self.layers[layer].parameter_mut(local_index)
+    }
+
+    /// Iterate over every parameter of the network in global index order
+    /// (layer by layer, weights row-major followed by biases)
+    pub fn parameters(&self) -> impl Iterator<Item = (ParameterInfo, &F)> {
+        self.layers
+            .iter()
+            .enumerate()
+            .flat_map(|(layer, l)| l.parameters().map(move |(role, p)| (layer, role, p)))
+            .enumerate()
+            .map(|(index, (layer, role, p))| (ParameterInfo { index, layer, role }, p))
+    }
+
+    /// Mutably iterate over every parameter of the network, see [`NeuralNetwork::parameters`]
+    pub fn parameters_mut(&mut self) -> impl Iterator<Item = (ParameterInfo, &mut F)> {
+        self.layers
+            .iter_mut()
+            .enumerate()
+            .flat_map(|(layer, l)| l.parameters_mut().map(move |(role, p)| (layer, role, p)))
+            .enumerate()
+            .map(|(index, (layer, role, p))| (ParameterInfo { index, layer, role }, p))
+    }
+
+    /// Map a global parameter index to a (layer index, index within layer) pair
+    fn locate(&self, mut index: usize) -> Option<(usize, usize)> {
+        for (layer_index, layer) in self.layers.iter().enumerate() {
+            if index < layer.num_parameters() {
+                return Some((layer_index, index));
+            }
+            index -= layer.num_parameters();
+        }
+        None
+    }
+}
+
+impl<F: 'static + Float, const N: usize, A: ActivationFn<F, N>> NeuralNetwork<F, N, A> {
     /// forward-pass a batch of input vectors through the network
-    pub fn forward(&mut self, inp: &Array2<Dual<F, N>>) -> Array2<Dual<F, N>> {
+    ///
+    /// # Panics
+    ///
+    /// Panics if the network has more parameters than the dual numbers can track (`N`)
+    pub fn forward(&self, inp: &Array2<Dual<F, N>>) -> Array2<Dual<F, N>> {
+        assert!(
+            self.num_parameters() <= N,
+            "network has {} parameters but dual numbers only track {}",
+            self.num_parameters(),
+            N
+        );
+        self.forward_with(inp, Dual::variable)
+    }
+
+    /// forward-pass a batch of input vectors through the network using any differentiable number type.
+    ///
+    /// Every parameter is lifted by calling `seed` with its value and global index (see
+    /// [`NeuralNetwork::parameter`]). This allows using number types whose number of tangents
+    /// is only known at runtime, like [`DynDual`] backed by dense [`Tangents`] or by
+    /// [`SparseTangents`], in which case `N` is irrelevant:
+    /// ```
+    /// # use deep_thought::prelude::*;
+    /// # use ndarray::prelude::*;
+    /// let net = NeuralNetwork::<f64>::new().add_layer(Layer::new(2, 1));
+    /// let tangents = Tangents::new(net.num_parameters());
+    /// let inp = array![[1.], [2.]].map(|&x| DynDual::constant(x));
+    /// let out = net.forward_with(&inp, |val, index| tangents.variable(val, index));
+    /// assert_eq!(out[[0, 0]].gradient().len(), 3);
+    /// ```
+    ///
+    /// Every intermediate value keeps its tangent row, see [`NeuralNetwork::forward_dyn`] for
+    /// a forward pass which releases them after every layer.
+    pub fn forward_with<D, S>(&self, inp: &Array2<D>, seed: S) -> Array2<D>
+    where
+        D: Float + From<F>,
+        S: Fn(F, usize) -> D,
+        A: ActivationOn<F, D>,
+    {
         let mut input = inp.to_owned();
-        for index in 0..self.layers.len() {
-            input = self.layers[index].forward(&input);
-        }
-        input.to_owned()
-    }
-}
-
-// /// A mutable iterator over the networks parameters. 
-// pub struct IterMut<'a, F, const N: usize> {
-//     index: usize,
-//     layer_index: usize,
-//     layers: &'a mut Vec<Layer<F, N>>,
-// }
-// 
-// impl<'a, F> Iterator for IterMut<'a, F, N>
-// where F: 'a {
-//     type Item = &'a F;
-// 
-//     #[inline]
-//     fn next(&mut self) -> Option<&'a mut F> {
-//         let layer = self.layers[self.layer_index];
-//         let width = layer.ncols();
-//         let height = layer.nrows();
-// 
-//         if self.layer_index < layer.len() {
-//             layer.get_mut((self.index / width, self.index % width))
-//         } else {
-//             self.layer_index += 1;
-//             self.index = 0;
-// 
-//             if self.layers.len() < self.layer_index {
-//                 self.layers[self.layer_index].get_mut((self.index / width, self.index % width))
-//             } else {
-//                 None
-//             }
-// 
-//         }
-//     }
-// }
+        let mut offset = 0;
+        for layer in self.layers.iter() {
+            input = layer.forward_with(&input, offset, &seed);
+            offset += layer.num_parameters();
+        }
+        input
+    }
+
+    /// forward-pass a batch of input vectors through the network using [`DynDual`] numbers whose
+    /// tangents are stored in `tangents`.
+    ///
+    /// Every parameter is seeded as the variable with its global index, like in
+    /// [`NeuralNetwork::forward_with`]. After every layer, the rows of all values except the
+    /// layer's output are released through [`TangentBuffer::retain`], so the buffer only grows
+    /// with the largest layer instead of the whole network:
+    /// ```
+    /// # use deep_thought::prelude::*;
+    /// # use ndarray::prelude::*;
+    /// let sizes = [2, 8, 8, 8, 1];
+    /// let net = sizes
+    ///     .windows(2)
+    ///     .fold(NeuralNetwork::<f64>::new(), |net, dims| {
+    ///         net.add_layer(Layer::new(dims[0], dims[1]).activation(Activation::Tanh))
+    ///     });
+    /// let tangents = Tangents::new(net.num_parameters());
+    /// let out = net.forward_dyn(&array![[1.], [2.]], &tangents);
+    /// assert_eq!(out[[0, 0]].gradient().len(), net.num_parameters());
+    /// ```
+    pub fn forward_dyn<'t, T>(&self, inp: &Array2<F>, tangents: &'t T) -> Array2<DynDual<'t, F, T>>
+    where
+        T: TangentBuffer<F>,
+        A: ActivationOn<F, DynDual<'t, F, T>>,
+    {
+        let seed = |val, index| tangents.variable(val, index);
+        let mut input = inp.map(|&x| DynDual::constant(x));
+        let mut offset = 0;
+        for layer in self.layers.iter() {
+            input = layer.forward_with(&input, offset, seed);
+            offset += layer.num_parameters();
+            let live: Vec<T::Row> = input.iter().filter_map(DynDual::row).collect();
+            tangents.retain(&live);
+        }
+        input
+    }
+
+    /// forward-pass a batch of input vectors through the network without tracking any derivatives.
+    ///
+    /// Computes the same values as [`NeuralNetwork::forward`] using plain floats, which is much
+    /// cheaper for inference.
+    pub fn predict(&self, inp: &Array2<F>) -> Array2<F> {
+        self.layers
+            .iter()
+            .fold(inp.to_owned(), |input, layer| layer.predict(&input))
+    }
+}
+
+impl<F: 'static + Float, const N: usize, A: ActivationFn<F, N>> NeuralNetwork<F, N, A> {
+    /// Compute the mean loss over a batch of input vectors together with its derivative with
+    /// respect to every parameter (in global index order), using the given [`Engine`].
+    ///
+    /// The gradient can be passed to [`Optimizer::apply_gradient`](crate::optimizer::Optimizer::apply_gradient).
+    pub fn loss_gradient<L: LossFn<F, N> + ?Sized>(
+        &self,
+        inp: &Array2<F>,
+        target: &Array2<F>,
+        loss: &L,
+        engine: Engine,
+    ) -> (F, Vec<F>) {
+        let num_parameters = self.num_parameters();
+        match engine {
+            Engine::Forward => {
+                let out = self.forward(&inp.map(|&x| Dual::constant(x)));
+                let l = loss.reduce(&out, target, Reduction::Mean);
+                (l.val, l.e[..num_parameters].to_vec())
+            }
+            Engine::Dynamic => {
+                let tangents = Tangents::new(num_parameters);
+                let out = self.forward_dyn(inp, &tangents);
+                let l = loss.reduce(&out, target, Reduction::Mean);
+                (l.val, dense_gradient(l.gradient(), num_parameters))
+            }
+            Engine::Sparse => {
+                let tangents = SparseTangents::new(num_parameters);
+                let out = self.forward_dyn(inp, &tangents);
+                let l = loss.reduce(&out, target, Reduction::Mean);
+                (l.val, dense_gradient(l.gradient(), num_parameters))
+            }
+            Engine::Reverse => {
+                let tape = Tape::new(num_parameters);
+                let out = self.forward_dyn(inp, &tape);
+                let l = loss.reduce(&out, target, Reduction::Mean);
+                (l.val, dense_gradient(l.gradient(), num_parameters))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<F, const N: usize, A> NeuralNetwork<F, N, A>
+where
+    F: 'static + Float + Send + Sync,
+    A: ActivationFn<F, N> + Sync,
+{
+    /// Like [`NeuralNetwork::loss_gradient`], but splits the batch's samples into up to `threads`
+    /// shards whose losses and gradients are computed in parallel. The shards run on rayon's
+    /// current thread pool, which can be chosen with [`rayon::ThreadPool::install`].
+    ///
+    /// The shards' mean losses and gradients are weighted by their number of samples, so the result
+    /// matches [`NeuralNetwork::loss_gradient`] up to rounding.
+    pub fn parallel_loss_gradient<L: LossFn<F, N> + Sync + ?Sized>(
+        &self,
+        inp: &Array2<F>,
+        target: &Array2<F>,
+        loss: &L,
+        engine: Engine,
+        threads: usize,
+    ) -> (F, Vec<F>) {
+        let num_samples = inp.ncols();
+        let shard_size = num_samples.div_ceil(threads.max(1)).max(1);
+        let shards: Vec<_> = inp
+            .axis_chunks_iter(Axis(1), shard_size)
+            .zip(target.axis_chunks_iter(Axis(1), shard_size))
+            .collect();
+        // collecting keeps the order of the shards, so the sums below are deterministic
+        let shards: Vec<_> = shards
+            .into_par_iter()
+            .map(|(inp, target)| {
+                let (l, gradient) =
+                    self.loss_gradient(&inp.to_owned(), &target.to_owned(), loss, engine);
+                let weight = F::from(inp.ncols()).unwrap() / F::from(num_samples).unwrap();
+                (
+                    l * weight,
+                    gradient.into_iter().map(|d| d * weight).collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+
+        let mut total = F::zero();
+        let mut gradient = vec![F::zero(); self.num_parameters()];
+        for (l, shard_gradient) in shards {
+            total = total + l;
+            for (d, shard_d) in gradient.iter_mut().zip(shard_gradient) {
+                *d = *d + shard_d;
+            }
+        }
+        (total, gradient)
+    }
+}
+
+/// Constants have an empty gradient, which is equivalent to all zeros
+fn dense_gradient<F: Float>(mut gradient: Vec<F>, num_parameters: usize) -> Vec<F> {
+    gradient.resize(num_parameters, F::zero());
+    gradient
+}
+
+impl<F: 'static + Float, const N: usize, A> Default for NeuralNetwork<F, N, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loss::{Loss, LossOn};
+    use std::thread;
+
+    const NUM_PARAMETERS: usize = 9;
+
+    fn network() -> NeuralNetwork<f64, NUM_PARAMETERS> {
+        let mut net = NeuralNetwork::new()
+            .add_layer(Layer::new(2, 2).activation(Activation::Sigmoid))
+            .add_layer(Layer::new(2, 1));
+        net.layers[0].W = array![[0.1, -0.2], [0.3, 0.4]];
+        net.layers[0].B = array![[0.05], [-0.05]];
+        net.layers[1].W = array![[0.7, -0.6]];
+        net.layers[1].B = array![[0.2]];
+        net
+    }
+
+    fn output(net: &NeuralNetwork<f64, NUM_PARAMETERS>, inp: &Array2<f64>) -> f64 {
+        net.forward(&inp.map(|&x| Dual::constant(x)))[[0, 0]].val
+    }
+
+    #[test]
+    fn parameters_follow_global_index() {
+        let mut net = network();
+        let expected: Vec<f64> = (0..net.num_parameters())
+            .map(|index| *net.parameter(index).unwrap())
+            .collect();
+
+        let found: Vec<f64> = net.parameters().map(|(_, &p)| p).collect();
+        assert_eq!(found, expected);
+
+        let infos: Vec<ParameterInfo> = net.parameters().map(|(info, _)| info).collect();
+        assert_eq!(infos.len(), NUM_PARAMETERS);
+        assert!(infos
+            .iter()
+            .enumerate()
+            .all(|(index, info)| info.index == index));
+        assert_eq!(
+            infos[5],
+            ParameterInfo {
+                index: 5,
+                layer: 0,
+                role: ParameterRole::Bias { row: 1 }
+            }
+        );
+        assert_eq!(
+            infos[7],
+            ParameterInfo {
+                index: 7,
+                layer: 1,
+                role: ParameterRole::Weight { row: 0, col: 1 }
+            }
+        );
+
+        for (info, p) in net.parameters_mut() {
+            *p = info.index as f64;
+        }
+        assert_eq!(net.layers[1].W, array![[6., 7.]]);
+        assert_eq!(*net.parameter(8).unwrap(), 8.);
+    }
+
+    #[test]
+    fn forward_seeds_parameters_by_global_index() {
+        let inp = array![[0.5], [-1.5]];
+        let net = network();
+        assert_eq!(net.num_parameters(), NUM_PARAMETERS);
+
+        let out = net.forward(&inp.map(|&x| Dual::constant(x)))[[0, 0]];
+
+        // compare every tangent against a central finite difference on the same parameter
+        let h = 1e-6;
+        let mut index = 0;
+        for layer_ix in 0..net.layers.len() {
+            for param in 0..net.layers[layer_ix].num_parameters() {
+                let perturb = |net: &mut NeuralNetwork<f64, NUM_PARAMETERS>, delta: f64| {
+                    let layer = &mut net.layers[layer_ix];
+                    let width = layer.W.len();
+                    if param < width {
+                        let ncols = layer.W.ncols();
+                        layer.W[[param / ncols, param % ncols]] += delta;
+                    } else {
+                        layer.B[[param - width, 0]] += delta;
+                    }
+                };
+
+                let mut plus = network();
+                perturb(&mut plus, h);
+                let mut minus = network();
+                perturb(&mut minus, -h);
+                let expected = (output(&plus, &inp) - output(&minus, &inp)) / (2. * h);
+
+                assert!((out.e[index] - expected).abs() < 1e-6);
+                index += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn forward_with_runtime_sized_duals_matches_forward() {
+        let inp = array![[0.5, 1.], [-1.5, 0.25]];
+        let net = network();
+        let fixed = net.forward(&inp.map(|&x| Dual::constant(x)));
+
+        let dense = Tangents::new(net.num_parameters());
+        let dynamic = net.forward_with(&inp.map(|&x| DynDual::constant(x)), |val, index| {
+            dense.variable(val, index)
+        });
+
+        let sparse = SparseTangents::new(net.num_parameters());
+        let sparse_out = net.forward_with(&inp.map(|&x| DynDual::constant(x)), |val, index| {
+            sparse.variable(val, index)
+        });
+
+        for ((a, b), c) in fixed.iter().zip(dynamic.iter()).zip(sparse_out.iter()) {
+            assert!((a.val - b.val).abs() < 1e-12);
+            assert!((a.val - c.val).abs() < 1e-12);
+            for ((x, y), z) in a.e.iter().zip(b.gradient()).zip(c.gradient()) {
+                assert!((x - y).abs() < 1e-12);
+                assert!((x - z).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn predict_matches_forward() {
+        let net = network();
+        let inp = array![[0.5, -1.], [2., 0.3]];
+        let expected = net.forward(&inp.map(|&x| Dual::constant(x)));
+        let found = net.predict(&inp);
+        assert_eq!(found.dim(), expected.dim());
+        for (a, b) in found.iter().zip(expected.iter()) {
+            assert!((a - b.val).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn networks_can_be_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<NeuralNetwork<f64, NUM_PARAMETERS>>();
+        assert_send_sync::<NeuralNetwork<f32, 0, Box<Activation<f32, 0>>>>();
+
+        let net = network();
+        let inputs: Vec<Array2<f64>> = (0..32).map(|i| array![[i as f64 / 8.], [-1.]]).collect();
+        let expected: Vec<f64> = inputs.iter().map(|inp| net.predict(inp)[[0, 0]]).collect();
+
+        // a pool of workers, each serving a share of the requests with the same network
+        let found: Vec<(f64, f64)> = thread::scope(|scope| {
+            let workers: Vec<_> = inputs
+                .chunks(8)
+                .map(|requests| {
+                    let net = &net;
+                    scope.spawn(move || {
+                        requests
+                            .iter()
+                            .map(|inp| (net.predict(inp)[[0, 0]], output(net, inp)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+
+        assert_eq!(found.len(), expected.len());
+        for (&(predicted, forward), &expected) in found.iter().zip(&expected) {
+            assert_eq!(predicted, expected);
+            assert!((forward - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn every_engine_computes_the_same_gradient() {
+        let inp = array![[0.5, 1., -0.3], [-1.5, 0.25, 2.]];
+        let target = array![[0.2, -0.4, 1.]];
+        let net = network();
+
+        let (expected_loss, expected) =
+            net.loss_gradient(&inp, &target, &Loss::MSE, Engine::Forward);
+        assert_eq!(expected.len(), NUM_PARAMETERS);
+
+        for engine in [Engine::Dynamic, Engine::Sparse, Engine::Reverse] {
+            let (loss, gradient) = net.loss_gradient(&inp, &target, &Loss::MSE, engine);
+            assert!((loss - expected_loss).abs() < 1e-12);
+            for (a, b) in gradient.iter().zip(&expected) {
+                assert!(
+                    (a - b).abs() < 1e-12,
+                    "{:?} disagrees with forward mode",
+                    engine
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn parallel_gradient_matches_single_thread() {
+        let inp = array![[0.5, 1., -0.3, 0.8, -1.1], [-1.5, 0.25, 2., 0.1, 0.6]];
+        let target = array![[0.2, -0.4, 1., 0.3, -0.7]];
+        let net = network();
+
+        let mut classifier = NeuralNetwork::<f64, 6>::new().add_layer(Layer::new(2, 2));
+        classifier.layers[0].W = array![[0.3, -0.1], [-0.4, 0.2]];
+        let classes = array![[1., 0., 0., 1., 1.], [0., 1., 1., 0., 0.]];
+
+        for engine in [
+            Engine::Forward,
+            Engine::Dynamic,
+            Engine::Sparse,
+            Engine::Reverse,
+        ] {
+            let (expected_loss, expected) = net.loss_gradient(&inp, &target, &Loss::MSE, engine);
+            let softmax = Loss::SoftmaxCrossEntropy;
+            let (expected_ce, expected_ce_gradient) =
+                classifier.loss_gradient(&inp, &classes, &softmax, engine);
+
+            for threads in [0, 1, 3, 8] {
+                let (loss, gradient) =
+                    net.parallel_loss_gradient(&inp, &target, &Loss::MSE, engine, threads);
+                assert!((loss - expected_loss).abs() < 1e-12);
+                for (a, b) in gradient.iter().zip(&expected) {
+                    assert!(
+                        (a - b).abs() < 1e-12,
+                        "{:?} with {} threads",
+                        engine,
+                        threads
+                    );
+                }
+
+                let (loss, gradient) =
+                    classifier.parallel_loss_gradient(&inp, &classes, &softmax, engine, threads);
+                assert!((loss - expected_ce).abs() < 1e-12);
+                for (a, b) in gradient.iter().zip(&expected_ce_gradient) {
+                    assert!(
+                        (a - b).abs() < 1e-12,
+                        "{:?} with {} threads",
+                        engine,
+                        threads
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn layers_accept_custom_activations() {
+        struct CustomTanh;
+
+        impl<D: Float + From<f64>> ActivationOn<f64, D> for CustomTanh {
+            fn compute(&self, inp: &Array2<D>) -> Array2<D> {
+                let two: D = 2.0.into();
+                inp.map(|&x| two / (D::one() + (-two * x).exp()) - D::one())
+            }
+        }
+
+        let mut builtin = network();
+        for layer in builtin.layers.iter_mut() {
+            layer.activation = Activation::Tanh;
+        }
+        let mut custom = NeuralNetwork::new();
+        for layer in builtin.layers.iter() {
+            custom = custom.add_layer(Layer {
+                W: layer.W.clone(),
+                B: layer.B.clone(),
+                activation: CustomTanh,
+            });
+        }
+
+        let inp = array![[0.5, 1., -0.3], [-1.5, 0.25, 2.]].map(|&x| Dual::constant(x));
+        for (a, b) in builtin
+            .forward(&inp)
+            .iter()
+            .zip(custom.forward(&inp).iter())
+        {
+            assert!((a.val - b.val).abs() < 1e-12);
+            for (x, y) in a.e.iter().zip(b.e.iter()) {
+                assert!((x - y).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn layers_can_mix_boxed_activations() {
+        /// x * sigmoid(x)
+        struct Swish;
+
+        impl<D: Float + From<f64>> ActivationOn<f64, D> for Swish {
+            fn compute(&self, inp: &Array2<D>) -> Array2<D> {
+                inp.map(|&x| x / (D::one() + (-x).exp()))
+            }
+        }
+
+        let mut builtin = network();
+        builtin.layers[0].activation = Activation::SiLU;
+        builtin.layers[1].activation = Activation::Tanh;
+        let activations: [Box<dyn ActivationFn<f64, NUM_PARAMETERS>>; 2] = [
+            Box::new(Swish),
+            Box::new(Activation::<f64, NUM_PARAMETERS>::Tanh),
+        ];
+        let mut mixed = NeuralNetwork::new();
+        for (layer, activation) in builtin.layers.iter().zip(activations) {
+            mixed = mixed.add_layer(Layer {
+                W: layer.W.clone(),
+                B: layer.B.clone(),
+                activation,
+            });
+        }
+
+        let inp = array![[0.5, 1., -0.3], [-1.5, 0.25, 2.]];
+        let target = array![[0.2, -0.4, 1.]];
+        for (a, b) in builtin.predict(&inp).iter().zip(mixed.predict(&inp).iter()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+        for engine in [
+            Engine::Forward,
+            Engine::Dynamic,
+            Engine::Sparse,
+            Engine::Reverse,
+        ] {
+            let (loss, gradient) = builtin.loss_gradient(&inp, &target, &Loss::MSE, engine);
+            let (mixed_loss, mixed_gradient) =
+                mixed.loss_gradient(&inp, &target, &Loss::MSE, engine);
+            assert!((loss - mixed_loss).abs() < 1e-12, "{:?}", engine);
+            for (a, b) in gradient.iter().zip(&mixed_gradient) {
+                assert!((a - b).abs() < 1e-12, "{:?}", engine);
+            }
+        }
+    }
+
+    #[test]
+    fn loss_gradient_accepts_custom_losses() {
+        struct WeightedMSE(f64);
+
+        impl<D: Float + From<f64>> LossOn<f64, D> for WeightedMSE {
+            fn compute(&self, output: &Array2<D>, target: &Array2<f64>) -> Array2<D> {
+                let weight: D = self.0.into();
+                Loss::MSE.compute(output, target).map(|&x| weight * x)
+            }
+        }
+
+        let inp = array![[0.5, 1., -0.3], [-1.5, 0.25, 2.]];
+        let target = array![[0.2, -0.4, 1.]];
+        let net = network();
+
+        let (mse, expected) = net.loss_gradient(&inp, &target, &Loss::MSE, Engine::Reverse);
+        let (weighted, gradient) =
+            net.loss_gradient(&inp, &target, &WeightedMSE(3.), Engine::Reverse);
+        assert!((weighted - 3. * mse).abs() < 1e-12);
+        for (a, b) in gradient.iter().zip(&expected) {
+            assert!((a - 3. * b).abs() < 1e-12);
+        }
+
+        // losses can be picked at runtime
+        let losses: [Box<dyn LossFn<f64, NUM_PARAMETERS>>; 2] =
+            [Box::new(Loss::MSE), Box::new(WeightedMSE(3.))];
+        for (loss, expected) in losses.iter().zip([mse, weighted]) {
+            for engine in [
+                Engine::Forward,
+                Engine::Dynamic,
+                Engine::Sparse,
+                Engine::Reverse,
+            ] {
+                let (l, _) = net.loss_gradient(&inp, &target, loss, engine);
+                assert!((l - expected).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn networks_can_be_built_from_runtime_sizes() {
+        // e.g. read from a config file
+        let sizes: Vec<usize> = "2 4 3 1".split(' ').map(|s| s.parse().unwrap()).collect();
+        let net = sizes
+            .windows(2)
+            .fold(NeuralNetwork::<f64>::new(), |net, dims| {
+                net.add_layer(Layer::new(dims[0], dims[1]).activation(Activation::Tanh))
+            });
+        assert_eq!(net.num_parameters(), 12 + 15 + 4);
+
+        let inp = array![[0.5, -1.], [1.5, 0.3]];
+        let target = array![[0.2, -0.4]];
+        let (loss, gradient) = net.loss_gradient(&inp, &target, &Loss::MSE, Engine::Reverse);
+        assert_eq!(gradient.len(), net.num_parameters());
+        for engine in [Engine::Dynamic, Engine::Sparse] {
+            let (l, g) = net.loss_gradient(&inp, &target, &Loss::MSE, engine);
+            assert!((l - loss).abs() < 1e-12);
+            for (a, b) in g.iter().zip(&gradient) {
+                assert!((a - b).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn forward_dyn_reuses_rows_of_previous_layers() {
+        let network = |depth| {
+            (0..depth).fold(NeuralNetwork::<f64>::new(), |net, _| {
+                net.add_layer(Layer::new(3, 3).activation(Activation::Tanh))
+            })
+        };
+        let num_rows = |net: NeuralNetwork<f64>| {
+            let tangents = Tangents::new(net.num_parameters());
+            net.forward_dyn(&array![[1.], [2.], [3.]], &tangents);
+            tangents.num_rows()
+        };
+        // every layer reuses the rows released by the previous one
+        assert_eq!(num_rows(network(2)), num_rows(network(6)));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::optimizer::Optimizer;
+use crate::prelude::*;
+use num_traits::Float;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Implements Adadelta, which scales every parameter's step by the ratio of the running
+/// averages of its recent updates and gradients, so no learning rate has to be tuned
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Adadelta<F, const N: usize> {
+    /// factor applied to every update
+    lr: F,
+    /// decay rate of the running averages
+    rho: F,
+    /// added to both running averages for numerical stability
+    epsilon: F,
+    /// running average of each parameter's squared gradient
+    s: Vec<F>,
+    /// running average of each parameter's squared update
+    delta: Vec<F>,
+}
+
+impl<F, const N: usize> Optimizer<F, N> for Adadelta<F, N>
+where
+    F: 'static + Float,
+{
+    fn new() -> Self {
+        Adadelta {
+            lr: F::one(),
+            rho: F::from(0.9).unwrap(),
+            epsilon: F::from(1e-6).unwrap(),
+            s: vec![],
+            delta: vec![],
+        }
+    }
+
+    fn lr(&self) -> F {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: F) {
+        self.lr = lr;
+    }
+
+    fn apply_gradient<A>(&mut self, net: &mut NeuralNetwork<F, N, A>, gradient: &[F]) {
+        // The number of parameters might only be known once the first gradient arrives
+        self.s.resize(gradient.len(), F::zero());
+        self.delta.resize(gradient.len(), F::zero());
+
+        let one = F::one();
+        let params = net.parameters_mut();
+        let state = self.s.iter_mut().zip(self.delta.iter_mut());
+        for (((_, param), (s, delta)), &d) in params.zip(state).zip(gradient) {
+            *s = self.rho * *s + (one - self.rho) * d * d;
+            let update = (*delta + self.epsilon).sqrt() / (*s + self.epsilon).sqrt() * d;
+            *delta = self.rho * *delta + (one - self.rho) * update * update;
+            *param = *param - self.lr * update;
+        }
+    }
+}
+
+impl<F, const N: usize> Adadelta<F, N> {
+    /// Set the factor applied to every update
+    pub fn learning_rate(mut self, lr: F) -> Self {
+        self.lr = lr;
+        self
+    }
+
+    /// Set the decay rate of the running averages
+    pub fn rho(mut self, rho: F) -> Self {
+        self.rho = rho;
+        self
+    }
+
+    /// Set the term added to both running averages for numerical stability
+    pub fn epsilon(mut self, epsilon: F) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::tests::fit_linear_function;
+
+    #[test]
+    fn adadelta_fits_linear_function() {
+        fit_linear_function(Adadelta::new().rho(0.95).epsilon(1e-4), 3000);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::optimizer::Optimizer;
+use crate::prelude::*;
+use num_traits::Float;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Implements Adagrad, which divides every parameter's step by the root of the sum
+/// of all of its squared gradients so far
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Adagrad<F, const N: usize> {
+    /// learning rate
+    lr: F,
+    /// added to the denominator for numerical stability
+    epsilon: F,
+    /// sum of each parameter's squared gradients
+    s: Vec<F>,
+}
+
+impl<F, const N: usize> Optimizer<F, N> for Adagrad<F, N>
+where
+    F: 'static + Float,
+{
+    fn new() -> Self {
+        Adagrad {
+            lr: F::from(0.01).unwrap(),
+            epsilon: F::from(1e-10).unwrap(),
+            s: vec![],
+        }
+    }
+
+    fn lr(&self) -> F {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: F) {
+        self.lr = lr;
+    }
+
+    fn apply_gradient<A>(&mut self, net: &mut NeuralNetwork<F, N, A>, gradient: &[F]) {
+        // The number of parameters might only be known once the first gradient arrives
+        self.s.resize(gradient.len(), F::zero());
+
+        for (s, &d) in self.s.iter_mut().zip(gradient) {
+            *s = *s + d * d;
+        }
+
+        for (((_, param), &s), &d) in net.parameters_mut().zip(&self.s).zip(gradient) {
+            *param = *param - self.lr * d / (s.sqrt() + self.epsilon);
+        }
+    }
+}
+
+impl<F, const N: usize> Adagrad<F, N> {
+    /// Set the learning rate
+    pub fn learning_rate(mut self, lr: F) -> Self {
+        self.lr = lr;
+        self
+    }
+
+    /// Set the term added to the denominator for numerical stability
+    pub fn epsilon(mut self, epsilon: F) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::tests::fit_linear_function;
+
+    #[test]
+    fn adagrad_fits_linear_function() {
+        fit_linear_function(Adagrad::new().learning_rate(0.5), 2000);
+    }
+}
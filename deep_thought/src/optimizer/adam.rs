@@ -0,0 +1,196 @@
+use crate::optimizer::Optimizer;
+use crate::prelude::*;
+use num_traits::Float;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Implements Adam, which scales every parameter's step by running estimates of the
+/// first and second moment of its gradient
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Adam<F, const N: usize> {
+    /// learning rate
+    lr: F,
+    /// decay rate of the first moment estimate
+    beta1: F,
+    /// decay rate of the second moment estimate
+    beta2: F,
+    /// added to the denominator for numerical stability
+    epsilon: F,
+    /// number of steps taken so far
+    t: i32,
+    /// first moment (mean) estimate of each parameter's gradient
+    m: Vec<F>,
+    /// second moment (uncentered variance) estimate of each parameter's gradient
+    v: Vec<F>,
+}
+
+impl<F: Float, const N: usize> Adam<F, N> {
+    /// Update the moment estimates and return the bias-corrected step direction of every parameter
+    fn direction(&mut self, gradient: &[F]) -> Vec<F> {
+        // The number of parameters might only be known once the first gradient arrives
+        self.m.resize(gradient.len(), F::zero());
+        self.v.resize(gradient.len(), F::zero());
+        self.t += 1;
+
+        let (one, beta1, beta2, epsilon) = (F::one(), self.beta1, self.beta2, self.epsilon);
+        let correction1 = one - beta1.powi(self.t);
+        let correction2 = one - beta2.powi(self.t);
+        self.m
+            .iter_mut()
+            .zip(self.v.iter_mut())
+            .zip(gradient)
+            .map(|((m, v), &d)| {
+                *m = beta1 * *m + (one - beta1) * d;
+                *v = beta2 * *v + (one - beta2) * d * d;
+                (*m / correction1) / ((*v / correction2).sqrt() + epsilon)
+            })
+            .collect()
+    }
+}
+
+impl<F, const N: usize> Optimizer<F, N> for Adam<F, N>
+where
+    F: 'static + Float,
+{
+    fn new() -> Self {
+        Adam {
+            lr: F::from(0.001).unwrap(),
+            beta1: F::from(0.9).unwrap(),
+            beta2: F::from(0.999).unwrap(),
+            epsilon: F::from(1e-8).unwrap(),
+            t: 0,
+            m: vec![],
+            v: vec![],
+        }
+    }
+
+    fn lr(&self) -> F {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: F) {
+        self.lr = lr;
+    }
+
+    fn apply_gradient<A>(&mut self, net: &mut NeuralNetwork<F, N, A>, gradient: &[F]) {
+        let direction = self.direction(gradient);
+        for ((_, param), d) in net.parameters_mut().zip(direction) {
+            *param = *param - self.lr * d;
+        }
+    }
+}
+
+impl<F, const N: usize> Adam<F, N> {
+    /// Set the learning rate
+    pub fn learning_rate(mut self, lr: F) -> Self {
+        self.lr = lr;
+        self
+    }
+
+    /// Set the decay rates of the first and second moment estimates
+    pub fn betas(mut self, beta1: F, beta2: F) -> Self {
+        self.beta1 = beta1;
+        self.beta2 = beta2;
+        self
+    }
+
+    /// Set the term added to the denominator for numerical stability
+    pub fn epsilon(mut self, epsilon: F) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+}
+
+/// Implements AdamW, which is [`Adam`] with weight decay that is decoupled from the gradient
+/// (so it is not rescaled by the moment estimates)
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AdamW<F, const N: usize> {
+    adam: Adam<F, N>,
+    /// how much every parameter is pulled towards zero each step, relative to the learning rate
+    weight_decay: F,
+}
+
+impl<F, const N: usize> Optimizer<F, N> for AdamW<F, N>
+where
+    F: 'static + Float,
+{
+    fn new() -> Self {
+        AdamW {
+            adam: Adam::new(),
+            weight_decay: F::from(0.01).unwrap(),
+        }
+    }
+
+    fn lr(&self) -> F {
+        self.adam.lr
+    }
+
+    fn set_lr(&mut self, lr: F) {
+        self.adam.lr = lr;
+    }
+
+    fn apply_gradient<A>(&mut self, net: &mut NeuralNetwork<F, N, A>, gradient: &[F]) {
+        let direction = self.adam.direction(gradient);
+        let lr = self.adam.lr;
+        for ((_, param), d) in net.parameters_mut().zip(direction) {
+            *param = *param - lr * (d + self.weight_decay * *param);
+        }
+    }
+}
+
+impl<F, const N: usize> AdamW<F, N> {
+    /// Set the learning rate
+    pub fn learning_rate(mut self, lr: F) -> Self {
+        self.adam = self.adam.learning_rate(lr);
+        self
+    }
+
+    /// Set the decay rates of the first and second moment estimates
+    pub fn betas(mut self, beta1: F, beta2: F) -> Self {
+        self.adam = self.adam.betas(beta1, beta2);
+        self
+    }
+
+    /// Set the term added to the denominator for numerical stability
+    pub fn epsilon(mut self, epsilon: F) -> Self {
+        self.adam = self.adam.epsilon(epsilon);
+        self
+    }
+
+    /// Set the weight decay
+    pub fn weight_decay(mut self, weight_decay: F) -> Self {
+        self.weight_decay = weight_decay;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::tests::fit_linear_function;
+
+    #[test]
+    fn adam_fits_linear_function() {
+        fit_linear_function(Adam::new().learning_rate(0.05), 1000);
+    }
+
+    #[test]
+    fn adamw_fits_linear_function() {
+        fit_linear_function(AdamW::new().learning_rate(0.05).weight_decay(0.), 1000);
+    }
+
+    #[test]
+    fn adamw_decays_weights_without_gradient() {
+        let mut net = NeuralNetwork::<f64, 2>::new().add_layer(Layer::new(1, 1));
+        net.layers[0].W[[0, 0]] = 2.;
+        net.layers[0].B[[0, 0]] = -1.;
+
+        let mut optim = AdamW::new().learning_rate(0.1).weight_decay(0.5);
+        optim.apply_gradient(&mut net, &[0., 0.]);
+        assert!((net.layers[0].W[[0, 0]] - 2. * 0.95).abs() < 1e-12);
+        assert!((net.layers[0].B[[0, 0]] + 0.95).abs() < 1e-12);
+    }
+}
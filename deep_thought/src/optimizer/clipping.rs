@@ -0,0 +1,343 @@
+use crate::error::Error;
+use crate::optimizer::Optimizer;
+use crate::prelude::*;
+use anyhow::Result;
+use num_traits::Float;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, Normal, StandardNormal};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// How gradients are clipped before they are applied
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Clip<F> {
+    /// Scale the whole gradient down if its euclidean norm exceeds the given value
+    Norm(F),
+    /// Clamp every component of the gradient to `[-value, value]`
+    Value(F),
+}
+
+impl<F: Float> Clip<F> {
+    /// Clip the gradient in place
+    pub fn apply(&self, gradient: &mut [F]) {
+        match *self {
+            Clip::Norm(max_norm) => {
+                let norm = gradient
+                    .iter()
+                    .fold(F::zero(), |acc, &d| acc + d * d)
+                    .sqrt();
+                if norm > max_norm {
+                    let scale = max_norm / norm;
+                    gradient.iter_mut().for_each(|d| *d = *d * scale);
+                }
+            }
+            Clip::Value(value) => {
+                gradient
+                    .iter_mut()
+                    .for_each(|d| *d = d.max(-value).min(value));
+            }
+        }
+    }
+}
+
+/// Wraps another [`Optimizer`] and safeguards the gradients before they reach it.
+///
+/// Steps whose loss or gradient contain NaN or infinite values are skipped, so the network's
+/// parameters are never corrupted. [`Clipped::try_step`] reports them as an
+/// [`Error::NonFiniteGradient`] instead. The remaining gradients are optionally clipped and
+/// perturbed with [annealed gaussian noise](https://arxiv.org/abs/1511.06807).
+/// ```
+/// # use deep_thought::prelude::*;
+/// # use deep_thought::optimizer::*;
+/// # use ndarray::prelude::*;
+/// let mut net = NeuralNetwork::<f64, 2>::new().add_layer(Layer::new(1, 1));
+/// let mut optim = Clipped::new(SGD::new().learning_rate(0.1)).clip(Clip::Norm(1.));
+///
+/// let out = net.forward(&array![[f64::NAN]].map(|&x| Dual::constant(x)));
+/// let loss = Loss::MSE.reduce(&out, &array![[1.]], Reduction::Mean);
+/// assert!(optim.try_step(&mut net, loss).is_err());
+/// assert_eq!(optim.skipped_steps(), 1);
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Clipped<F, O> {
+    optimizer: O,
+    clip: Option<Clip<F>>,
+    /// scale `eta` and decay rate `gamma` of the noise variance `eta / (1 + t)^gamma`
+    noise: Option<(F, F)>,
+    /// source of the gradient noise, serialized so resumed runs draw the same noise
+    rng: ChaCha8Rng,
+    /// number of applied steps
+    t: i32,
+    /// number of steps skipped because of non-finite values
+    skipped: usize,
+}
+
+impl<F, O> Clipped<F, O> {
+    /// Wrap `optimizer`, without clipping or noise
+    pub fn new(optimizer: O) -> Self {
+        Clipped {
+            optimizer,
+            clip: None,
+            noise: None,
+            rng: ChaCha8Rng::from_entropy(),
+            t: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Set how the gradients are clipped
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bound of `clip` is negative or NaN
+    pub fn clip(mut self, clip: Clip<F>) -> Self
+    where
+        F: Float,
+    {
+        let (Clip::Norm(bound) | Clip::Value(bound)) = clip;
+        assert!(bound >= F::zero(), "clipping bound must be non-negative");
+        self.clip = Some(clip);
+        self
+    }
+
+    /// Add gaussian noise with the variance `eta / (1 + t)^gamma` to the gradient of step `t`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `eta` is negative or NaN, or if `gamma` is NaN
+    pub fn noise(mut self, eta: F, gamma: F) -> Self
+    where
+        F: Float,
+    {
+        assert!(eta >= F::zero(), "noise scale must be non-negative");
+        assert!(!gamma.is_nan(), "noise decay rate must not be NaN");
+        self.noise = Some((eta, gamma));
+        self
+    }
+
+    /// Seed the gradient noise, which is seeded from system entropy by default
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self
+    }
+
+    /// The wrapped optimizer
+    pub fn inner(&self) -> &O {
+        &self.optimizer
+    }
+
+    /// Number of steps that were skipped because of NaN or infinite values
+    pub fn skipped_steps(&self) -> usize {
+        self.skipped
+    }
+}
+
+impl<F, O> Clipped<F, O>
+where
+    F: Float,
+    StandardNormal: Distribution<F>,
+{
+    /// Like [`Optimizer::apply_gradient`], but returns an error instead of silently skipping
+    /// gradients with NaN or infinite values. Steps are also skipped with an
+    /// [`Error::InvalidNoise`] if the variance of the gradient noise overflows to an invalid value.
+    pub fn try_apply_gradient<A, const N: usize>(
+        &mut self,
+        net: &mut NeuralNetwork<F, N, A>,
+        gradient: &[F],
+    ) -> Result<()>
+    where
+        O: Optimizer<F, N>,
+    {
+        self.check(gradient)?;
+        let mut gradient = gradient.to_vec();
+        if let Some(clip) = &self.clip {
+            clip.apply(&mut gradient);
+        }
+        if let Some((eta, gamma)) = self.noise {
+            let variance = eta / (F::one() + F::from(self.t).unwrap()).powf(gamma);
+            let std_dev = variance.sqrt();
+            let dist = Normal::new(F::zero(), std_dev).map_err(|_| Error::InvalidNoise {
+                std_dev: std_dev.to_f64().unwrap_or(f64::NAN),
+            })?;
+            gradient
+                .iter_mut()
+                .for_each(|d| *d = *d + dist.sample(&mut self.rng));
+        }
+        self.t += 1;
+        self.optimizer.apply_gradient(net, &gradient);
+        Ok(())
+    }
+
+    /// Like [`Optimizer::step`], but returns an error instead of silently skipping
+    /// losses with NaN or infinite values
+    pub fn try_step<A, const N: usize>(
+        &mut self,
+        net: &mut NeuralNetwork<F, N, A>,
+        loss: Dual<F, N>,
+    ) -> Result<()>
+    where
+        O: Optimizer<F, N>,
+    {
+        if !loss.is_finite() {
+            let values: Vec<F> = std::iter::once(loss.val).chain(loss.e).collect();
+            self.check(&values)?;
+        }
+        self.try_apply_gradient(net, &loss.e)
+    }
+
+    /// Count the step as skipped if any of the values are NaN or infinite
+    fn check(&mut self, values: &[F]) -> Result<()> {
+        let nan = values.iter().filter(|x| x.is_nan()).count();
+        let infinite = values.iter().filter(|x| x.is_infinite()).count();
+        if nan + infinite == 0 {
+            return Ok(());
+        }
+        self.skipped += 1;
+        Err(Error::NonFiniteGradient { nan, infinite }.into())
+    }
+}
+
+impl<F, O, const N: usize> Optimizer<F, N> for Clipped<F, O>
+where
+    F: Float,
+    O: Optimizer<F, N>,
+    StandardNormal: Distribution<F>,
+{
+    fn new() -> Self {
+        Clipped::new(O::new())
+    }
+
+    fn lr(&self) -> F {
+        self.optimizer.lr()
+    }
+
+    fn set_lr(&mut self, lr: F) {
+        self.optimizer.set_lr(lr);
+    }
+
+    fn apply_gradient<A>(&mut self, net: &mut NeuralNetwork<F, N, A>, gradient: &[F]) {
+        let _ = self.try_apply_gradient(net, gradient);
+    }
+
+    fn step<A>(&mut self, net: &mut NeuralNetwork<F, N, A>, loss: Dual<F, N>) {
+        let _ = self.try_step(net, loss);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::{tests::fit_linear_function, SGD};
+
+    #[test]
+    fn clipping() {
+        let mut gradient = [3., -4.];
+        Clip::Norm(10.).apply(&mut gradient);
+        assert_eq!(gradient, [3., -4.]);
+        Clip::Norm(1.).apply(&mut gradient);
+        assert!((gradient[0] - 0.6).abs() < 1e-12 && (gradient[1] + 0.8).abs() < 1e-12);
+
+        let mut gradient = [3., -4., 0.5];
+        Clip::Value(1.).apply(&mut gradient);
+        assert_eq!(gradient, [1., -1., 0.5]);
+    }
+
+    #[test]
+    fn skips_non_finite_steps() {
+        let mut net = NeuralNetwork::<f64, 2>::new().add_layer(Layer::new(1, 1));
+        let before = net.layers[0].W.clone();
+        let mut optim = Clipped::new(SGD::new());
+
+        // the trait methods skip silently
+        optim.apply_gradient(&mut net, &[f64::NAN, 1.]);
+        assert_eq!(net.layers[0].W, before);
+
+        let err = optim
+            .try_apply_gradient(&mut net, &[f64::INFINITY, f64::NAN])
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::NonFiniteGradient {
+                nan: 1,
+                infinite: 1
+            })
+        ));
+        assert_eq!(net.layers[0].W, before);
+        assert_eq!(optim.skipped_steps(), 2);
+
+        // finite gradients are still applied afterwards
+        optim.try_apply_gradient(&mut net, &[1., 1.]).unwrap();
+        assert!((net.layers[0].W[[0, 0]] - (before[[0, 0]] - 0.01)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn noise_perturbs_gradient() {
+        let mut net = NeuralNetwork::<f64, 2>::new().add_layer(Layer::new(1, 1));
+        let mut optim = Clipped::new(SGD::new().learning_rate(1.)).noise(1., 0.55);
+        optim.try_apply_gradient(&mut net, &[0., 0.]).unwrap();
+        assert_ne!(net.layers[0].B[[0, 0]], 0.);
+    }
+
+    #[test]
+    fn overflowing_noise_skips_step() {
+        let mut net = NeuralNetwork::<f64, 2>::new().add_layer(Layer::new(1, 1));
+        // the variance doubles to infinity on the second step
+        let mut optim = Clipped::new(SGD::new()).noise(f64::MAX, -1.);
+        optim.try_apply_gradient(&mut net, &[0., 0.]).unwrap();
+        let before = net.layers[0].W.clone();
+        let err = optim.try_apply_gradient(&mut net, &[0., 0.]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::InvalidNoise { std_dev }) if std_dev.is_infinite()
+        ));
+        assert_eq!(net.layers[0].W, before);
+    }
+
+    #[test]
+    #[should_panic(expected = "clipping bound must be non-negative")]
+    fn negative_clipping_bound() {
+        Clipped::<f64, SGD<f64, 2>>::new(SGD::new()).clip(Clip::Value(-1.));
+    }
+
+    #[test]
+    #[should_panic(expected = "clipping bound must be non-negative")]
+    fn nan_clipping_bound() {
+        Clipped::<f64, SGD<f64, 2>>::new(SGD::new()).clip(Clip::Norm(f64::NAN));
+    }
+
+    #[test]
+    #[should_panic(expected = "noise scale must be non-negative")]
+    fn negative_noise_scale() {
+        Clipped::<f64, SGD<f64, 2>>::new(SGD::new()).noise(-1., 0.55);
+    }
+
+    #[test]
+    #[should_panic(expected = "noise scale must be non-negative")]
+    fn nan_noise_scale() {
+        Clipped::<f64, SGD<f64, 2>>::new(SGD::new()).noise(f64::NAN, 0.55);
+    }
+
+    #[test]
+    fn seeded_noise_is_reproducible() {
+        let train = |seed| {
+            let mut net = NeuralNetwork::<f64, 2>::new().add_layer(Layer::new(1, 1));
+            net.layers[0].W.fill(0.5);
+            let mut optim = Clipped::new(SGD::new()).noise(1., 0.55).seed(seed);
+            for _ in 0..3 {
+                optim.try_apply_gradient(&mut net, &[0.1, -0.2]).unwrap();
+            }
+            net.parameters().map(|(_, &p)| p).collect::<Vec<_>>()
+        };
+        assert_eq!(train(7), train(7));
+        assert_ne!(train(7), train(8));
+    }
+
+    #[test]
+    fn clipped_fits_linear_function() {
+        let optim = Clipped::new(SGD::new().learning_rate(0.1)).clip(Clip::Norm(1.));
+        fit_linear_function(optim, 500);
+    }
+}
@@ -1,5 +1,115 @@
+mod adadelta;
+mod adagrad;
+mod adam;
+mod clipping;
 mod optim_trait;
+mod rmsprop;
+mod scheduler;
 mod sgd;
 
+pub use adadelta::*;
+pub use adagrad::*;
+pub use adam::*;
+pub use clipping::*;
 pub use optim_trait::*;
+pub use rmsprop::*;
+pub use scheduler::*;
 pub use sgd::*;
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use ndarray::prelude::*;
+
+    /// Train a single linear neuron on y = 2x + 1, which is a convex problem,
+    /// and assert that the optimizer converges to the solution
+    pub(crate) fn fit_linear_function<O: Optimizer<f64, 2>>(mut optim: O, steps: usize) {
+        let inputs = array![[-1., 0., 1., 2.]];
+        let targets = array![[-1., 1., 3., 5.]];
+
+        let mut net = NeuralNetwork::<f64, 2>::new().add_layer(Layer::new(1, 1));
+        // start from a fixed point (biases are already zero) so the result is deterministic
+        net.layers[0].W[[0, 0]] = 0.;
+        for _ in 0..steps {
+            let (_, gradient) = net.loss_gradient(&inputs, &targets, &Loss::MSE, Engine::Forward);
+            optim.apply_gradient(&mut net, &gradient);
+        }
+
+        let (w, b) = (net.layers[0].W[[0, 0]], net.layers[0].B[[0, 0]]);
+        assert!((w - 2.).abs() < 1e-2, "weight {} did not converge to 2", w);
+        assert!((b - 1.).abs() < 1e-2, "bias {} did not converge to 1", b);
+    }
+
+    /// Take `steps` steps on y = 2x + 1, advancing the scheduler after every step
+    #[cfg(feature = "serde")]
+    fn train<O: Optimizer<f64, 2>, S: LrScheduler<f64>>(
+        net: &mut NeuralNetwork<f64, 2>,
+        optim: &mut O,
+        scheduler: &mut S,
+        steps: usize,
+    ) {
+        let inputs = array![[-1., 0., 1., 2.]];
+        let targets = array![[-1., 1., 3., 5.]];
+        for _ in 0..steps {
+            let (_, gradient) = net.loss_gradient(&inputs, &targets, &Loss::MSE, Engine::Forward);
+            optim.apply_gradient(net, &gradient);
+            scheduler.step(optim, None);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn resume_from_checkpoint() {
+        let mut net = NeuralNetwork::<f64, 2>::new().add_layer(Layer::new(1, 1));
+        let mut optim = Adam::new().learning_rate(0.1);
+        let mut scheduler = LinearWarmup::new(5, CosineAnnealing::new(0.1, 10).warm_restarts(2));
+        train(&mut net, &mut optim, &mut scheduler, 10);
+
+        let checkpoint = serde_json::to_string(&(&net, &optim, &scheduler)).unwrap();
+        let (mut resumed_net, mut resumed_optim, mut resumed_scheduler): (
+            NeuralNetwork<f64, 2>,
+            Adam<f64, 2>,
+            LinearWarmup<CosineAnnealing<f64>>,
+        ) = serde_json::from_str(&checkpoint).unwrap();
+
+        train(&mut net, &mut optim, &mut scheduler, 10);
+        train(
+            &mut resumed_net,
+            &mut resumed_optim,
+            &mut resumed_scheduler,
+            10,
+        );
+        assert_eq!(resumed_net.layers[0].W, net.layers[0].W);
+        assert_eq!(resumed_net.layers[0].B, net.layers[0].B);
+        assert_eq!(resumed_optim.lr(), optim.lr());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn resume_noisy_clipped_from_checkpoint() {
+        let mut net = NeuralNetwork::<f64, 2>::new().add_layer(Layer::new(1, 1));
+        let mut optim = Clipped::new(SGD::new().learning_rate(0.1))
+            .clip(Clip::Norm(1.))
+            .noise(0.1, 0.55);
+        let mut scheduler = StepDecay::new(0.1, 5, 0.5);
+        train(&mut net, &mut optim, &mut scheduler, 10);
+
+        let checkpoint = serde_json::to_string(&(&net, &optim, &scheduler)).unwrap();
+        let (mut resumed_net, mut resumed_optim, mut resumed_scheduler): (
+            NeuralNetwork<f64, 2>,
+            Clipped<f64, SGD<f64, 2>>,
+            StepDecay<f64>,
+        ) = serde_json::from_str(&checkpoint).unwrap();
+
+        train(&mut net, &mut optim, &mut scheduler, 10);
+        train(
+            &mut resumed_net,
+            &mut resumed_optim,
+            &mut resumed_scheduler,
+            10,
+        );
+        assert_eq!(resumed_net.layers[0].W, net.layers[0].W);
+        assert_eq!(resumed_net.layers[0].B, net.layers[0].B);
+    }
+}
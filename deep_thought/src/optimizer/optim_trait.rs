@@ -1,13 +1,28 @@
 use crate::autograd::Dual;
 use crate::neural_network::NeuralNetwork;
-use num_traits::Float;
 
 /// Implement this for your custom optimizers
+///
+/// With the `serde` feature, the built-in optimizers and [`LrScheduler`](crate::optimizer::LrScheduler)s
+/// serialize their full state (step counts, moment estimates, velocities and schedule positions),
+/// so a training run can be checkpointed together with its [`NeuralNetwork`] and resumed exactly.
 pub trait Optimizer<F, const N: usize> {
     /// Create a new instance of the Optimizer
     fn new() -> Self;
 
+    /// Current learning rate
+    fn lr(&self) -> F;
+
+    /// Change the learning rate, e.g. according to a [`LrScheduler`](crate::optimizer::LrScheduler)
+    fn set_lr(&mut self, lr: F);
+
+    /// Optimizes the provided network's parameters based on the derivative of the loss
+    /// with respect to each of them, in global parameter index order
+    fn apply_gradient<A>(&mut self, net: &mut NeuralNetwork<F, N, A>, gradient: &[F]);
+
     /// Optimizes the provided network's parameters based on their corresponding delta values
     /// (which are already computed at this point)
-    fn step(&mut self, net: &mut NeuralNetwork<F, N>, loss: Dual<F, N>);
+    fn step<A>(&mut self, net: &mut NeuralNetwork<F, N, A>, loss: Dual<F, N>) {
+        self.apply_gradient(net, &loss.e)
+    }
 }
@@ -0,0 +1,87 @@
+use crate::optimizer::Optimizer;
+use crate::prelude::*;
+use num_traits::Float;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Implements RMSProp, which divides every parameter's step by a running average
+/// of the magnitude of its recent gradients
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RMSProp<F, const N: usize> {
+    /// learning rate
+    lr: F,
+    /// decay rate of the squared gradient average
+    rho: F,
+    /// added to the denominator for numerical stability
+    epsilon: F,
+    /// running average of each parameter's squared gradient
+    s: Vec<F>,
+}
+
+impl<F, const N: usize> Optimizer<F, N> for RMSProp<F, N>
+where
+    F: 'static + Float,
+{
+    fn new() -> Self {
+        RMSProp {
+            lr: F::from(0.01).unwrap(),
+            rho: F::from(0.99).unwrap(),
+            epsilon: F::from(1e-8).unwrap(),
+            s: vec![],
+        }
+    }
+
+    fn lr(&self) -> F {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: F) {
+        self.lr = lr;
+    }
+
+    fn apply_gradient<A>(&mut self, net: &mut NeuralNetwork<F, N, A>, gradient: &[F]) {
+        // The number of parameters might only be known once the first gradient arrives
+        self.s.resize(gradient.len(), F::zero());
+
+        for (s, &d) in self.s.iter_mut().zip(gradient) {
+            *s = self.rho * *s + (F::one() - self.rho) * d * d;
+        }
+
+        for (((_, param), &s), &d) in net.parameters_mut().zip(&self.s).zip(gradient) {
+            *param = *param - self.lr * d / (s.sqrt() + self.epsilon);
+        }
+    }
+}
+
+impl<F, const N: usize> RMSProp<F, N> {
+    /// Set the learning rate
+    pub fn learning_rate(mut self, lr: F) -> Self {
+        self.lr = lr;
+        self
+    }
+
+    /// Set the decay rate of the squared gradient average
+    pub fn rho(mut self, rho: F) -> Self {
+        self.rho = rho;
+        self
+    }
+
+    /// Set the term added to the denominator for numerical stability
+    pub fn epsilon(mut self, epsilon: F) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::tests::fit_linear_function;
+
+    #[test]
+    fn rmsprop_fits_linear_function() {
+        fit_linear_function(RMSProp::new().learning_rate(0.01), 2000);
+    }
+}
@@ -0,0 +1,601 @@
+use crate::optimizer::Optimizer;
+use num_traits::Float;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Varies the learning rate of an [`Optimizer`] over the course of training
+///
+/// A scheduler starts at position zero and is advanced once per step or once per epoch,
+/// depending on how often [`LrScheduler::step`] is called:
+/// ```
+/// # use deep_thought::optimizer::*;
+/// let mut scheduler = StepDecay::new(0.1, 2, 0.5);
+/// let mut optim = SGD::<f64, 0>::new().learning_rate(scheduler.lr());
+/// for epoch in 0..4 {
+///     // train for one epoch...
+///     scheduler.step(&mut optim, None);
+/// }
+/// assert_eq!(optim.lr(), 0.025);
+/// ```
+///
+/// Schedulers can be used as trait objects, e.g. to pick one from a configuration file at
+/// runtime. `Box<dyn LrScheduler<F>>` implements the trait as well, so it can still
+/// [`step`](LrScheduler::step).
+pub trait LrScheduler<F: Float> {
+    /// Learning rate at the current position
+    fn lr(&self) -> F;
+
+    /// Move to the next position. `metric` is the latest value of a monitored quantity
+    /// (like the validation loss), which is only used by schedulers that react to it
+    fn advance(&mut self, metric: Option<F>);
+
+    /// Advance the schedule and apply the new learning rate to `optimizer`
+    fn step<O: Optimizer<F, N>, const N: usize>(&mut self, optimizer: &mut O, metric: Option<F>)
+    where
+        Self: Sized,
+    {
+        self.advance(metric);
+        optimizer.set_lr(self.lr());
+    }
+}
+
+impl<F: Float, S: LrScheduler<F> + ?Sized> LrScheduler<F> for Box<S> {
+    fn lr(&self) -> F {
+        (**self).lr()
+    }
+
+    fn advance(&mut self, metric: Option<F>) {
+        (**self).advance(metric)
+    }
+}
+
+/// Convert a position into the learning rate's number type
+fn position<F: Float>(t: usize) -> F {
+    F::from(t).unwrap()
+}
+
+/// Cosine interpolation from `start` (at `pct = 0`) to `end` (at `pct = 1`)
+fn cosine<F: Float>(start: F, end: F, pct: F) -> F {
+    let two = F::one() + F::one();
+    let pi = F::from(std::f64::consts::PI).unwrap();
+    end + (start - end) / two * (F::one() + (pi * pct).cos())
+}
+
+/// Keeps the learning rate constant, useful as the schedule following a [`LinearWarmup`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Constant<F> {
+    lr: F,
+}
+
+impl<F> Constant<F> {
+    /// Create a constant schedule
+    pub fn new(lr: F) -> Self {
+        Constant { lr }
+    }
+}
+
+impl<F: Float> LrScheduler<F> for Constant<F> {
+    fn lr(&self) -> F {
+        self.lr
+    }
+
+    fn advance(&mut self, _metric: Option<F>) {}
+}
+
+/// Multiplies the learning rate by `gamma` every `step_size` positions
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StepDecay<F> {
+    lr: F,
+    step_size: usize,
+    gamma: F,
+    /// current position
+    t: usize,
+}
+
+impl<F> StepDecay<F> {
+    /// Create a step decay schedule starting at the learning rate `lr`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `step_size` is zero
+    pub fn new(lr: F, step_size: usize, gamma: F) -> Self {
+        assert!(step_size > 0, "step size must be positive");
+        StepDecay {
+            lr,
+            step_size,
+            gamma,
+            t: 0,
+        }
+    }
+}
+
+impl<F: Float> LrScheduler<F> for StepDecay<F> {
+    fn lr(&self) -> F {
+        self.lr * self.gamma.powi((self.t / self.step_size) as i32)
+    }
+
+    fn advance(&mut self, _metric: Option<F>) {
+        self.t += 1;
+    }
+}
+
+/// Multiplies the learning rate by `gamma` every position
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExponentialDecay<F> {
+    lr: F,
+    gamma: F,
+    /// current position
+    t: usize,
+}
+
+impl<F> ExponentialDecay<F> {
+    /// Create an exponential decay schedule starting at the learning rate `lr`
+    pub fn new(lr: F, gamma: F) -> Self {
+        ExponentialDecay { lr, gamma, t: 0 }
+    }
+}
+
+impl<F: Float> LrScheduler<F> for ExponentialDecay<F> {
+    fn lr(&self) -> F {
+        self.lr * self.gamma.powi(self.t as i32)
+    }
+
+    fn advance(&mut self, _metric: Option<F>) {
+        self.t += 1;
+    }
+}
+
+/// Anneals the learning rate from its initial value to a minimum following half a cosine
+/// wave over `period` positions, optionally restarting afterwards (SGDR)
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CosineAnnealing<F> {
+    lr: F,
+    min_lr: F,
+    period: usize,
+    /// factor applied to the period after every restart, restarts are disabled if `None`
+    restart_mult: Option<usize>,
+    /// current position
+    t: usize,
+}
+
+impl<F: Float> CosineAnnealing<F> {
+    /// Create a cosine annealing schedule starting at the learning rate `lr`
+    /// and reaching zero after `period` positions
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero
+    pub fn new(lr: F, period: usize) -> Self {
+        assert!(period > 0, "period must be positive");
+        CosineAnnealing {
+            lr,
+            min_lr: F::zero(),
+            period,
+            restart_mult: None,
+            t: 0,
+        }
+    }
+
+    /// Set the learning rate reached at the end of a period
+    pub fn min_lr(mut self, min_lr: F) -> Self {
+        self.min_lr = min_lr;
+        self
+    }
+
+    /// Restart at the initial learning rate after every period, multiplying the length
+    /// of the period by `period_mult` each time
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period_mult` is zero
+    pub fn warm_restarts(mut self, period_mult: usize) -> Self {
+        assert!(period_mult > 0, "period multiplier must be positive");
+        self.restart_mult = Some(period_mult);
+        self
+    }
+}
+
+impl<F: Float> LrScheduler<F> for CosineAnnealing<F> {
+    fn lr(&self) -> F {
+        let (t, period) = match self.restart_mult {
+            None => (self.t.min(self.period), self.period),
+            Some(1) => (self.t % self.period, self.period),
+            Some(mult) => {
+                // the periods grow geometrically, so this takes O(log t) iterations
+                let (mut t, mut period) = (self.t, self.period);
+                while t >= period {
+                    t -= period;
+                    period = period.saturating_mul(mult);
+                }
+                (t, period)
+            }
+        };
+        let pct = position::<F>(t) / position(period);
+        cosine(self.lr, self.min_lr, pct)
+    }
+
+    fn advance(&mut self, _metric: Option<F>) {
+        self.t += 1;
+    }
+}
+
+/// Linearly increases the learning rate to the one of the wrapped schedule over `steps` positions,
+/// then follows the wrapped schedule
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LinearWarmup<S> {
+    steps: usize,
+    after: S,
+    /// current position
+    t: usize,
+}
+
+impl<S> LinearWarmup<S> {
+    /// Create a warmup, after which the schedule `after` is started
+    pub fn new(steps: usize, after: S) -> Self {
+        LinearWarmup { steps, after, t: 0 }
+    }
+}
+
+impl<F: Float, S: LrScheduler<F>> LrScheduler<F> for LinearWarmup<S> {
+    fn lr(&self) -> F {
+        if self.t < self.steps {
+            self.after.lr() * position(self.t + 1) / position(self.steps)
+        } else {
+            self.after.lr()
+        }
+    }
+
+    fn advance(&mut self, metric: Option<F>) {
+        if self.t < self.steps {
+            self.t += 1;
+        } else {
+            self.after.advance(metric);
+        }
+    }
+}
+
+/// The one cycle policy: anneals the learning rate from `max_lr / div_factor` up to `max_lr`
+/// and then down to a small minimum, all within `total_steps` positions
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OneCycle<F> {
+    max_lr: F,
+    total_steps: usize,
+    /// fraction of the cycle spent increasing the learning rate
+    pct_start: F,
+    /// the initial learning rate is `max_lr / div_factor`
+    div_factor: F,
+    /// the final learning rate is the initial one divided by `final_div_factor`
+    final_div_factor: F,
+    /// current position
+    t: usize,
+}
+
+impl<F: Float> OneCycle<F> {
+    /// Create a one cycle schedule peaking at `max_lr`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `total_steps` is zero
+    pub fn new(max_lr: F, total_steps: usize) -> Self {
+        assert!(total_steps > 0, "total steps must be positive");
+        OneCycle {
+            max_lr,
+            total_steps,
+            pct_start: F::from(0.3).unwrap(),
+            div_factor: F::from(25).unwrap(),
+            final_div_factor: F::from(1e4).unwrap(),
+            t: 0,
+        }
+    }
+
+    /// Set the fraction of the cycle spent increasing the learning rate.
+    /// The learning rate starts at `max_lr` if the increase would take less than one position
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pct_start` is not within `[0, 1]`
+    pub fn pct_start(mut self, pct_start: F) -> Self {
+        assert!(
+            pct_start >= F::zero() && pct_start <= F::one(),
+            "pct_start must be within [0, 1]"
+        );
+        self.pct_start = pct_start;
+        self
+    }
+
+    /// Set the factor between the maximum and the initial learning rate
+    pub fn div_factor(mut self, div_factor: F) -> Self {
+        self.div_factor = div_factor;
+        self
+    }
+
+    /// Set the factor between the initial and the final learning rate
+    pub fn final_div_factor(mut self, final_div_factor: F) -> Self {
+        self.final_div_factor = final_div_factor;
+        self
+    }
+}
+
+impl<F: Float> LrScheduler<F> for OneCycle<F> {
+    fn lr(&self) -> F {
+        let initial = self.max_lr / self.div_factor;
+        let last = position::<F>(self.total_steps - 1);
+        let peak = self.pct_start * position(self.total_steps) - F::one();
+        let t = position::<F>(self.t).min(last);
+        let min_lr = initial / self.final_div_factor;
+        // compare before dividing, since either phase may be shorter than one position
+        if t < peak {
+            cosine(initial, self.max_lr, t / peak)
+        } else if t < last {
+            cosine(self.max_lr, min_lr, (t - peak) / (last - peak))
+        } else if peak < last {
+            min_lr
+        } else {
+            self.max_lr
+        }
+    }
+
+    fn advance(&mut self, _metric: Option<F>) {
+        self.t += 1;
+    }
+}
+
+/// Multiplies the learning rate by `factor` once the monitored metric (e.g. validation loss)
+/// has not improved for more than `patience` positions
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ReduceOnPlateau<F> {
+    lr: F,
+    factor: F,
+    patience: usize,
+    /// minimum decrease of the metric to count as an improvement
+    min_delta: F,
+    /// lower bound of the learning rate
+    min_lr: F,
+    /// lowest metric seen so far
+    best: Option<F>,
+    /// number of positions since the metric last improved
+    bad_epochs: usize,
+}
+
+impl<F: Float> ReduceOnPlateau<F> {
+    /// Create a schedule starting at the learning rate `lr`, which is multiplied by 0.1 once the
+    /// metric has not improved for 10 positions
+    pub fn new(lr: F) -> Self {
+        ReduceOnPlateau {
+            lr,
+            factor: F::from(0.1).unwrap(),
+            patience: 10,
+            min_delta: F::from(1e-4).unwrap(),
+            min_lr: F::zero(),
+            best: None,
+            bad_epochs: 0,
+        }
+    }
+
+    /// Set the factor applied to the learning rate on a plateau
+    pub fn factor(mut self, factor: F) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    /// Set the number of positions without improvement that are tolerated
+    pub fn patience(mut self, patience: usize) -> Self {
+        self.patience = patience;
+        self
+    }
+
+    /// Set the minimum decrease of the metric to count as an improvement
+    pub fn min_delta(mut self, min_delta: F) -> Self {
+        self.min_delta = min_delta;
+        self
+    }
+
+    /// Set the lower bound of the learning rate
+    pub fn min_lr(mut self, min_lr: F) -> Self {
+        self.min_lr = min_lr;
+        self
+    }
+}
+
+impl<F: Float> LrScheduler<F> for ReduceOnPlateau<F> {
+    fn lr(&self) -> F {
+        self.lr
+    }
+
+    /// Positions without a metric are ignored
+    fn advance(&mut self, metric: Option<F>) {
+        let metric = match metric {
+            Some(metric) => metric,
+            None => return,
+        };
+        match self.best {
+            Some(best) if metric >= best - self.min_delta => self.bad_epochs += 1,
+            _ => {
+                self.best = Some(metric);
+                self.bad_epochs = 0;
+            }
+        }
+        if self.bad_epochs > self.patience {
+            self.lr = (self.lr * self.factor).max(self.min_lr);
+            self.bad_epochs = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::SGD;
+
+    /// Learning rates of the first `n` positions
+    fn schedule<S: LrScheduler<f64>>(mut scheduler: S, n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|_| {
+                let lr = scheduler.lr();
+                scheduler.advance(None);
+                lr
+            })
+            .collect()
+    }
+
+    fn assert_schedule(found: Vec<f64>, expected: &[f64]) {
+        assert_eq!(found.len(), expected.len());
+        for (a, b) in found.iter().zip(expected) {
+            assert!((a - b).abs() < 1e-12, "{:?} != {:?}", found, expected);
+        }
+    }
+
+    #[test]
+    fn decays() {
+        assert_schedule(
+            schedule(StepDecay::new(1., 2, 0.5), 5),
+            &[1., 1., 0.5, 0.5, 0.25],
+        );
+        assert_schedule(
+            schedule(ExponentialDecay::new(1., 0.5), 4),
+            &[1., 0.5, 0.25, 0.125],
+        );
+    }
+
+    #[test]
+    fn cosine_annealing() {
+        assert_schedule(
+            schedule(CosineAnnealing::new(1., 2).min_lr(0.2), 4),
+            &[1., 0.6, 0.2, 0.2],
+        );
+        // periods of 2 and 4 positions
+        assert_schedule(
+            schedule(CosineAnnealing::new(1., 2).warm_restarts(2), 7),
+            &[
+                1.,
+                0.5,
+                1.,
+                0.5 + 0.5 / 2f64.sqrt(),
+                0.5,
+                0.5 - 0.5 / 2f64.sqrt(),
+                1.,
+            ],
+        );
+    }
+
+    #[test]
+    fn cosine_annealing_far_into_the_schedule() {
+        let mut scheduler = CosineAnnealing::new(1., 4).warm_restarts(1);
+        scheduler.t = usize::MAX - 3;
+        // usize::MAX is 3 modulo 4
+        assert_schedule(schedule(scheduler, 3), &[1., 0.5 + 0.5 / 2f64.sqrt(), 0.5]);
+
+        // the period would overflow before reaching the end of the schedule
+        let mut scheduler = CosineAnnealing::new(1., usize::MAX / 2).warm_restarts(3);
+        scheduler.t = usize::MAX - 1;
+        let lr = scheduler.lr();
+        assert!((0. ..=1.).contains(&lr));
+    }
+
+    #[test]
+    fn boxed_schedulers() {
+        let mut schedulers: Vec<Box<dyn LrScheduler<f64>>> = vec![
+            Box::new(StepDecay::new(1., 1, 0.5)),
+            Box::new(LinearWarmup::new(4, Constant::new(1.))),
+        ];
+        let mut optim = SGD::<f64, 0>::new();
+        for scheduler in schedulers.iter_mut() {
+            scheduler.step(&mut optim, None);
+            assert_eq!(optim.lr(), 0.5);
+        }
+
+        let boxed: Box<dyn LrScheduler<f64>> = Box::new(ExponentialDecay::new(1., 0.5));
+        assert_schedule(schedule(boxed, 3), &[1., 0.5, 0.25]);
+    }
+
+    #[test]
+    fn linear_warmup() {
+        let warmup = LinearWarmup::new(4, ExponentialDecay::new(1., 0.5));
+        assert_schedule(schedule(warmup, 6), &[0.25, 0.5, 0.75, 1., 1., 0.5]);
+    }
+
+    #[test]
+    fn one_cycle() {
+        let lrs = schedule(OneCycle::new(1., 10), 12);
+        assert!((lrs[0] - 0.04).abs() < 1e-12);
+        assert!((lrs[2] - 1.).abs() < 1e-12);
+        assert!((lrs[9] - 0.04 / 1e4).abs() < 1e-12);
+        assert_eq!(lrs[10], lrs[9]);
+        assert!(lrs[..3].windows(2).all(|w| w[0] < w[1]));
+        assert!(lrs[2..10].windows(2).all(|w| w[0] > w[1]));
+    }
+
+    #[test]
+    fn one_cycle_with_short_phases() {
+        // the increase would end at position 0
+        let lrs = schedule(OneCycle::new(1., 10).pct_start(0.1), 10);
+        assert_eq!(lrs[0], 1.);
+        assert!((lrs[9] - 0.04 / 1e4).abs() < 1e-12);
+        assert!(lrs.windows(2).all(|w| w[0] > w[1]));
+
+        // no decrease at all
+        let lrs = schedule(OneCycle::new(1., 10).pct_start(1.), 11);
+        assert!((lrs[0] - 0.04).abs() < 1e-12);
+        assert_eq!(lrs[9], 1.);
+        assert_eq!(lrs[10], 1.);
+        assert!(lrs[..10].windows(2).all(|w| w[0] < w[1]));
+
+        for lr in schedule(OneCycle::new(1., 1), 2) {
+            assert!(lr.is_finite());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "step size must be positive")]
+    fn step_decay_without_steps() {
+        StepDecay::new(1., 0, 0.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "period must be positive")]
+    fn cosine_annealing_without_period() {
+        CosineAnnealing::new(1., 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "period multiplier must be positive")]
+    fn cosine_annealing_without_period_mult() {
+        CosineAnnealing::new(1., 2).warm_restarts(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "total steps must be positive")]
+    fn one_cycle_without_steps() {
+        OneCycle::new(1., 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "pct_start must be within [0, 1]")]
+    fn one_cycle_with_invalid_pct_start() {
+        OneCycle::new(1., 10).pct_start(1.5);
+    }
+
+    #[test]
+    fn reduce_on_plateau() {
+        let mut scheduler = ReduceOnPlateau::new(1.).factor(0.5).patience(1);
+        let mut lrs = vec![];
+        for &loss in &[3., 2., 2., 2., 1.5, 1.5, 1.5, 1.5] {
+            scheduler.advance(Some(loss));
+            lrs.push(scheduler.lr());
+        }
+        assert_eq!(lrs, [1., 1., 1., 0.5, 0.5, 0.5, 0.25, 0.25]);
+
+        // missing metrics do not count as plateaus
+        scheduler.advance(None);
+        scheduler.advance(None);
+        assert_eq!(scheduler.lr(), 0.25);
+    }
+}
@@ -1,42 +1,62 @@
-use crate::autograd::Dual;
 use crate::optimizer::Optimizer;
 use crate::prelude::*;
-use ndarray::prelude::*;
 use num_traits::Float;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 /// Implements stochastic gradient descent (optionally with momentum)
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SGD<F, const N: usize> {
     /// learning rate
     lr: F,
     /// how much the previous change affects the current change
     momentum: F,
+    /// whether to evaluate the momentum ahead of the current parameters (Nesterov momentum)
+    nesterov: bool,
     /// velocity of each parameter
-    v: [F; N],
+    v: Vec<F>,
 }
 
 impl<F, const N: usize> Optimizer<F, N> for SGD<F, N>
 where
-    F: Float,
+    F: 'static + Float,
 {
     fn new() -> Self {
         SGD {
             lr: F::from(0.01).unwrap(),
             momentum: F::zero(),
-            v: [F::zero(); N],
+            nesterov: false,
+            v: vec![],
         }
     }
 
-    fn step(&mut self, net: &mut NeuralNetwork<F, N>, loss: Dual<F, N>) {
-        // Update parameter velocities, not sure if the formula is correct
-        let zipped = self.v.zip(loss.e);
-        self.v = zipped.map(|(v, d)| self.momentum * v + self.lr * d);
+    fn lr(&self) -> F {
+        self.lr
+    }
 
-        // Update the network's parameters
+    fn set_lr(&mut self, lr: F) {
+        self.lr = lr;
+    }
+
+    fn apply_gradient<A>(&mut self, net: &mut NeuralNetwork<F, N, A>, gradient: &[F]) {
+        // The number of parameters might only be known once the first gradient arrives
+        self.v.resize(gradient.len(), F::zero());
 
-        //     // update network parameters
-        //     layer.W = &layer.W + &self.v_weight[index];
-        //     layer.B = &layer.B + &self.v_bias[index];
-        // }
+        // Update parameter velocities
+        for (v, &d) in self.v.iter_mut().zip(gradient) {
+            *v = self.momentum * *v + self.lr * d;
+        }
+
+        // Update the network's parameters
+        for (((_, param), &v), &d) in net.parameters_mut().zip(&self.v).zip(gradient) {
+            if self.nesterov {
+                *param = *param - (self.momentum * v + self.lr * d);
+            } else {
+                *param = *param - v;
+            }
+        }
     }
 }
 
@@ -52,4 +72,42 @@ impl<F, const N: usize> SGD<F, N> {
         self.momentum = momentum;
         self
     }
+
+    /// Enable or disable Nesterov momentum
+    pub fn nesterov(mut self, nesterov: bool) -> Self {
+        self.nesterov = nesterov;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::tests::fit_linear_function;
+    use ndarray::prelude::*;
+
+    #[test]
+    fn step_fits_linear_function() {
+        // y = 2x + 1
+        let inputs = array![[-1., 0., 1., 2.]].map(|&x| Dual::constant(x));
+        let targets = array![[-1., 1., 3., 5.]];
+
+        let mut net = NeuralNetwork::<f64, 2>::new().add_layer(Layer::new(1, 1));
+        let mut optim = SGD::new().learning_rate(0.1).momentum(0.5);
+
+        for _ in 0..200 {
+            let out = net.forward(&inputs);
+            let loss = Loss::MSE.reduce(&out, &targets, Reduction::Mean);
+            optim.step(&mut net, loss);
+        }
+
+        assert!((net.layers[0].W[[0, 0]] - 2.).abs() < 1e-3);
+        assert!((net.layers[0].B[[0, 0]] - 1.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn nesterov_fits_linear_function() {
+        let optim = SGD::new().learning_rate(0.1).momentum(0.9).nesterov(true);
+        fit_linear_function(optim, 300);
+    }
 }
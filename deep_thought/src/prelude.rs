@@ -1 +1,4 @@
-pub use crate::{activation::*, autograd::*, dataset::*, loss::*, neural_network::*, optimizer};
+pub use crate::{
+    activation::*, autograd::*, dataset::*, loss::*, metrics, neural_network::*, optimizer,
+    trainer::*,
+};
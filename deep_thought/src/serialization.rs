@@ -0,0 +1,378 @@
+//! Save networks to disk and load them again
+//!
+//! A saved network starts with a [`Header`] describing its layers, followed by the layers themselves.
+//! The header is validated before the layers are read, so incompatible files are rejected with an
+//! [`Error`] instead of producing a broken network.
+//! ```no_run
+//! # use deep_thought::prelude::*;
+//! # fn main() -> anyhow::Result<()> {
+//! let net = NeuralNetwork::<f64, 9>::new()
+//!     .add_layer(Layer::new(2, 2).activation(Activation::Sigmoid))
+//!     .add_layer(Layer::new(2, 1));
+//! net.save("xor.json")?;
+//!
+//! let net = NeuralNetwork::<f64, 9>::load("xor.json")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::Error;
+use crate::neural_network::{Layer, NeuralNetwork};
+use anyhow::Result;
+use ndarray::IxDyn;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Version of the on-disk format written by [`NeuralNetwork::save`]
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Encoding of a saved network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Human readable JSON
+    Json,
+    /// Compact binary encoding using [bincode](https://docs.rs/bincode)
+    Binary,
+}
+
+impl Format {
+    /// Files ending in `.json` are stored as JSON, every other file is binary
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => Format::Json,
+            _ => Format::Binary,
+        }
+    }
+}
+
+/// Describes a saved network
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    /// Version of the format, see [`FORMAT_VERSION`]
+    pub version: u32,
+    /// Name of the network's number type, like `f64`
+    pub dtype: String,
+    /// Total number of trainable parameters
+    pub num_parameters: usize,
+    /// Shape and activation of every layer
+    pub layers: Vec<LayerHeader>,
+}
+
+/// Describes a single layer of a saved network
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayerHeader {
+    /// Number of inputs, which is the number of columns of the weight matrix
+    pub input_dim: usize,
+    /// Number of outputs, which is the number of rows of the weight matrix and the bias vector
+    pub output_dim: usize,
+    /// The layer's activation function, serialized as JSON
+    pub activation: String,
+}
+
+impl Header {
+    /// Check whether a network with the given number type and const `N` can be loaded from this header
+    fn validate<F, const N: usize>(&self) -> Result<(), Error> {
+        if self.version != FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion {
+                found: self.version,
+                supported: FORMAT_VERSION,
+            });
+        }
+        let dtype = std::any::type_name::<F>();
+        if self.dtype != dtype {
+            return Err(Error::MismatchedDtype {
+                expected: dtype.to_string(),
+                found: self.dtype.clone(),
+            });
+        }
+        if self.num_parameters > N {
+            return Err(Error::TooManyParameters {
+                capacity: N,
+                found: self.num_parameters,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Name of an activation function as it is stored in a [`LayerHeader`]
+fn activation_name<A: Serialize>(activation: &A) -> String {
+    serde_json::to_string(activation).unwrap_or_default()
+}
+
+/// A saved network as it is written to disk
+#[derive(Serialize)]
+struct SavedNetwork<'a, L> {
+    header: Header,
+    layers: &'a [L],
+}
+
+impl<F: Serialize, const N: usize, A: Serialize> NeuralNetwork<F, N, A> {
+    /// Describe the network's layers
+    pub fn header(&self) -> Header {
+        Header {
+            version: FORMAT_VERSION,
+            dtype: std::any::type_name::<F>().to_string(),
+            num_parameters: self.layers.iter().map(|l| l.W.len() + l.B.len()).sum(),
+            layers: self
+                .layers
+                .iter()
+                .map(|layer| LayerHeader {
+                    input_dim: layer.W.ncols(),
+                    output_dim: layer.W.nrows(),
+                    activation: activation_name(&layer.activation),
+                })
+                .collect(),
+        }
+    }
+
+    /// Save the network to `path`, using the [`Format`] given by the file extension
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let format = Format::from_path(&path);
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer, format)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Write the network to `writer`
+    pub fn write_to<W: Write>(&self, mut writer: W, format: Format) -> Result<()> {
+        let header = self.header();
+        match format {
+            Format::Json => serde_json::to_writer(
+                writer,
+                &SavedNetwork {
+                    header,
+                    layers: &self.layers,
+                },
+            )?,
+            Format::Binary => {
+                // the header is written separately so it can be validated before reading the layers
+                bincode::serialize_into(&mut writer, &header)?;
+                bincode::serialize_into(&mut writer, &self.layers)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<F, const N: usize, A> NeuralNetwork<F, N, A>
+where
+    F: Serialize + DeserializeOwned,
+    A: Serialize + DeserializeOwned,
+{
+    /// Load a network from `path`, using the [`Format`] given by the file extension
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let format = Format::from_path(&path);
+        Self::read_from(BufReader::new(File::open(path)?), format)
+    }
+
+    /// Read a network from `reader`
+    ///
+    /// Fails if the header was written by another format version, for another number type,
+    /// for more than `N` parameters or if it does not describe the shapes and activations of
+    /// the stored layers.
+    pub fn read_from<R: Read>(mut reader: R, format: Format) -> Result<Self> {
+        let (header, layers): (Header, Vec<Layer<F, N, A>>) = match format {
+            Format::Json => {
+                let mut saved: serde_json::Value = serde_json::from_reader(reader)?;
+                let header: Header = serde_json::from_value(saved["header"].take())?;
+                header.validate::<F, N>()?;
+                (header, serde_json::from_value(saved["layers"].take())?)
+            }
+            Format::Binary => {
+                let header: Header = bincode::deserialize_from(&mut reader)?;
+                header.validate::<F, N>()?;
+                (header, bincode::deserialize_from(&mut reader)?)
+            }
+        };
+
+        if layers.len() != header.layers.len() {
+            return Err(Error::MismatchedDimensions {
+                expected: IxDyn(&[header.layers.len()]),
+                found: IxDyn(&[layers.len()]),
+            }
+            .into());
+        }
+        for (index, (expected, layer)) in header.layers.iter().zip(&layers).enumerate() {
+            let shapes = [
+                (layer.W.shape(), [expected.output_dim, expected.input_dim]),
+                (layer.B.shape(), [expected.output_dim, 1]),
+            ];
+            for (found, expected) in shapes {
+                if found != expected {
+                    return Err(Error::MismatchedDimensions {
+                        expected: IxDyn(&expected),
+                        found: IxDyn(found),
+                    }
+                    .into());
+                }
+            }
+            let activation = activation_name(&layer.activation);
+            if activation != expected.activation {
+                return Err(Error::MismatchedActivation {
+                    layer: index,
+                    expected: expected.activation.clone(),
+                    found: activation,
+                }
+                .into());
+            }
+        }
+
+        let num_parameters = layers.iter().map(|l| l.W.len() + l.B.len()).sum();
+        if num_parameters != header.num_parameters {
+            return Err(Error::MismatchedParameterCount {
+                expected: header.num_parameters,
+                found: num_parameters,
+            }
+            .into());
+        }
+        Ok(NeuralNetwork { layers })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activation::Activation;
+    use ndarray::prelude::*;
+
+    fn network() -> NeuralNetwork<f64, 9> {
+        NeuralNetwork::new()
+            .add_layer(Layer::new(2, 2).activation(Activation::LeakyReLU(0.1)))
+            .add_layer(Layer::new(2, 1).activation(Activation::Sigmoid))
+    }
+
+    fn encode<F: Serialize, const N: usize>(net: &NeuralNetwork<F, N>, format: Format) -> Vec<u8> {
+        let mut bytes = vec![];
+        net.write_to(&mut bytes, format).unwrap();
+        bytes
+    }
+
+    fn error<T>(result: Result<T>) -> Error {
+        match result {
+            Ok(_) => panic!("expected an error"),
+            Err(err) => err.downcast().unwrap(),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let net = network();
+        let inp = array![[0.3, -1.2], [2., 0.7]];
+        let expected = net.predict(&inp);
+
+        for &format in &[Format::Json, Format::Binary] {
+            let bytes = encode(&net, format);
+            let loaded = NeuralNetwork::<f64, 9>::read_from(&bytes[..], format).unwrap();
+            assert_eq!(loaded.header(), net.header());
+            for (a, b) in loaded.layers.iter().zip(&net.layers) {
+                assert_eq!(a.W, b.W);
+                assert_eq!(a.B, b.B);
+            }
+            assert_eq!(loaded.predict(&inp), expected);
+        }
+        assert!(encode(&net, Format::Binary).len() < encode(&net, Format::Json).len());
+    }
+
+    #[test]
+    fn save_and_load_files() {
+        let net = network();
+        for extension in &["json", "bin"] {
+            // unique per process, so concurrent test runs do not share files
+            let name = format!(
+                "deep_thought_{}_save_test.{}",
+                std::process::id(),
+                extension
+            );
+            let path = std::env::temp_dir().join(name);
+            net.save(&path).unwrap();
+            let loaded = NeuralNetwork::<f64, 9>::load(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(loaded.layers[1].W, net.layers[1].W);
+        }
+        assert_eq!(Format::from_path("model.JSON"), Format::Json);
+        assert_eq!(Format::from_path("model.bin"), Format::Binary);
+    }
+
+    #[test]
+    fn rejects_incompatible_headers() {
+        let bytes = encode(&network(), Format::Binary);
+        assert!(matches!(
+            error(NeuralNetwork::<f64, 8>::read_from(
+                &bytes[..],
+                Format::Binary
+            )),
+            Error::TooManyParameters {
+                capacity: 8,
+                found: 9
+            }
+        ));
+        assert!(matches!(
+            error(NeuralNetwork::<f32, 9>::read_from(
+                &bytes[..],
+                Format::Binary
+            )),
+            Error::MismatchedDtype { .. }
+        ));
+
+        let mut saved: serde_json::Value =
+            serde_json::from_slice(&encode(&network(), Format::Json)).unwrap();
+        saved["header"]["version"] = (FORMAT_VERSION + 1).into();
+        let bytes = serde_json::to_vec(&saved).unwrap();
+        assert!(matches!(
+            error(NeuralNetwork::<f64, 9>::read_from(&bytes[..], Format::Json)),
+            Error::UnsupportedVersion { .. }
+        ));
+    }
+
+    #[test]
+    fn rejects_header_not_matching_layers() {
+        let mut saved: serde_json::Value =
+            serde_json::from_slice(&encode(&network(), Format::Json)).unwrap();
+        saved["header"]["num_parameters"] = 8.into();
+        let bytes = serde_json::to_vec(&saved).unwrap();
+        assert!(matches!(
+            error(NeuralNetwork::<f64, 9>::read_from(&bytes[..], Format::Json)),
+            Error::MismatchedParameterCount {
+                expected: 8,
+                found: 9
+            }
+        ));
+
+        saved["header"]["num_parameters"] = 9.into();
+        saved["header"]["layers"][0]["input_dim"] = 3.into();
+        let bytes = serde_json::to_vec(&saved).unwrap();
+        assert!(matches!(
+            error(NeuralNetwork::<f64, 9>::read_from(&bytes[..], Format::Json)),
+            Error::MismatchedDimensions { .. }
+        ));
+    }
+
+    #[test]
+    fn rejects_layers_not_matching_header() {
+        let original: serde_json::Value =
+            serde_json::from_slice(&encode(&network(), Format::Json)).unwrap();
+
+        // a bias row instead of a column with the same number of parameters
+        let mut saved = original.clone();
+        saved["layers"][0]["B"]["dim"] = vec![1, 2].into();
+        let bytes = serde_json::to_vec(&saved).unwrap();
+        match error(NeuralNetwork::<f64, 9>::read_from(&bytes[..], Format::Json)) {
+            Error::MismatchedDimensions { expected, found } => {
+                assert_eq!((expected, found), (IxDyn(&[2, 1]), IxDyn(&[1, 2])));
+            }
+            err => panic!("unexpected error {}", err),
+        }
+
+        let mut saved = original;
+        saved["layers"][1]["activation"] = "Tanh".into();
+        let bytes = serde_json::to_vec(&saved).unwrap();
+        assert!(matches!(
+            error(NeuralNetwork::<f64, 9>::read_from(&bytes[..], Format::Json)),
+            Error::MismatchedActivation { layer: 1, .. }
+        ));
+    }
+}
@@ -0,0 +1,769 @@
+use crate::activation::ActivationFn;
+use crate::dataset::{Dataset, SampleIterator};
+use crate::error::Error;
+use crate::loss::{LossFn, Reduction};
+use crate::metrics::Metric;
+use crate::neural_network::{Engine, NeuralNetwork};
+use crate::optimizer::Optimizer;
+use anyhow::Result;
+use ndarray::prelude::*;
+use num_traits::Float;
+#[cfg(feature = "rayon")]
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::fmt::Display;
+
+/// Returned by [`Callback`]s to decide whether training goes on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    /// Keep training
+    Continue,
+    /// Stop training after the current batch or epoch
+    Stop,
+}
+
+/// Statistics of a single optimization step
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchLogs<F> {
+    pub epoch: usize,
+    /// index of the batch within the epoch
+    pub batch: usize,
+    /// loss of the batch before the step
+    pub loss: F,
+}
+
+/// Statistics of a single epoch
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpochLogs<F> {
+    pub epoch: usize,
+    /// mean loss of the training batches, each evaluated before its step
+    pub loss: F,
+    /// metrics on the training data at the end of the epoch
+    pub metrics: Vec<(String, F)>,
+    /// mean loss on the test data at the end of the epoch, if there is any test data and
+    /// it was evaluated in this epoch (see [`Trainer::validate_every`])
+    pub val_loss: Option<F>,
+    /// metrics on the test data at the end of the epoch, empty if `val_loss` is `None`
+    pub val_metrics: Vec<(String, F)>,
+}
+
+impl<F: Copy> EpochLogs<F> {
+    /// Value of the training metric with the given name
+    pub fn metric(&self, name: &str) -> Option<F> {
+        find(&self.metrics, name)
+    }
+
+    /// Value of the test metric with the given name
+    pub fn val_metric(&self, name: &str) -> Option<F> {
+        find(&self.val_metrics, name)
+    }
+}
+
+fn find<F: Copy>(metrics: &[(String, F)], name: &str) -> Option<F> {
+    metrics
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|&(_, value)| value)
+}
+
+/// Logs of every epoch of a [`Trainer::fit`] call
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct History<F> {
+    pub epochs: Vec<EpochLogs<F>>,
+}
+
+impl<F: Copy> History<F> {
+    /// Mean training loss of every epoch
+    pub fn loss(&self) -> Vec<F> {
+        self.epochs.iter().map(|e| e.loss).collect()
+    }
+
+    /// Test loss of every epoch in which the test data was evaluated
+    pub fn val_loss(&self) -> Vec<F> {
+        self.epochs.iter().filter_map(|e| e.val_loss).collect()
+    }
+}
+
+/// Hooks into the training loop of a [`Trainer`], e.g. for logging, early stopping or checkpointing
+pub trait Callback<F, const N: usize, A> {
+    /// Called at the start of every call to [`Trainer::fit`], e.g. to reset state of previous calls
+    fn on_train_begin(&mut self, _net: &NeuralNetwork<F, N, A>) {}
+
+    /// Called after every optimization step
+    fn on_batch_end(&mut self, _net: &NeuralNetwork<F, N, A>, _logs: &BatchLogs<F>) -> Control {
+        Control::Continue
+    }
+
+    /// Called after every epoch, once the epoch's statistics are known
+    fn on_epoch_end(&mut self, _net: &NeuralNetwork<F, N, A>, _logs: &EpochLogs<F>) -> Control {
+        Control::Continue
+    }
+
+    /// Called once training has finished or was stopped
+    fn on_train_end(&mut self, _net: &mut NeuralNetwork<F, N, A>, _history: &History<F>) {}
+}
+
+/// Prints the losses and metrics every few epochs
+pub struct Logger {
+    every: usize,
+}
+
+impl Logger {
+    /// Print the statistics of every `every`-th epoch
+    ///
+    /// # Panics
+    ///
+    /// Panics if `every` is zero
+    pub fn new(every: usize) -> Self {
+        assert!(every > 0, "logging interval must be positive");
+        Logger { every }
+    }
+}
+
+impl<F: Display, const N: usize, A> Callback<F, N, A> for Logger {
+    fn on_epoch_end(&mut self, _net: &NeuralNetwork<F, N, A>, logs: &EpochLogs<F>) -> Control {
+        if logs.epoch.is_multiple_of(self.every) {
+            print!("epoch {}: loss {:.6}", logs.epoch, logs.loss);
+            for (name, value) in &logs.metrics {
+                print!(", {} {:.6}", name, value);
+            }
+            if let Some(val_loss) = &logs.val_loss {
+                print!(", val_loss {:.6}", val_loss);
+            }
+            for (name, value) in &logs.val_metrics {
+                print!(", val_{} {:.6}", name, value);
+            }
+            println!();
+        }
+        Control::Continue
+    }
+}
+
+/// Stops training once the loss on the test data has not improved for a number of evaluations,
+/// and restores the parameters that achieved the lowest test loss when training ends.
+///
+/// Epochs without a test loss (see [`Trainer::validate_every`]) are ignored.
+pub struct EarlyStopping<F> {
+    /// number of evaluations without improvement before training is stopped
+    patience: usize,
+    /// minimum decrease of the test loss to count as an improvement
+    min_delta: F,
+    /// whether to restore the best parameters at the end of training
+    restore_best: bool,
+    /// lowest test loss seen so far
+    best: Option<F>,
+    /// parameters (in global index order) of the network that achieved the lowest test loss
+    best_parameters: Vec<F>,
+    /// number of evaluations since the test loss last improved
+    wait: usize,
+}
+
+impl<F: Float> EarlyStopping<F> {
+    /// Stop training after `patience` evaluations without improvement
+    pub fn new(patience: usize) -> Self {
+        EarlyStopping {
+            patience,
+            min_delta: F::zero(),
+            restore_best: true,
+            best: None,
+            best_parameters: vec![],
+            wait: 0,
+        }
+    }
+
+    /// Set the minimum decrease of the test loss to count as an improvement
+    pub fn min_delta(mut self, min_delta: F) -> Self {
+        self.min_delta = min_delta;
+        self
+    }
+
+    /// Whether to restore the best parameters at the end of training (default `true`)
+    pub fn restore_best(mut self, restore_best: bool) -> Self {
+        self.restore_best = restore_best;
+        self
+    }
+}
+
+impl<F: 'static + Float, const N: usize, A> Callback<F, N, A> for EarlyStopping<F> {
+    /// Every call to [`Trainer::fit`] starts without a best test loss
+    fn on_train_begin(&mut self, _net: &NeuralNetwork<F, N, A>) {
+        self.best = None;
+        self.best_parameters.clear();
+        self.wait = 0;
+    }
+
+    fn on_epoch_end(&mut self, net: &NeuralNetwork<F, N, A>, logs: &EpochLogs<F>) -> Control {
+        let val_loss = match logs.val_loss {
+            Some(val_loss) => val_loss,
+            None => return Control::Continue,
+        };
+        match self.best {
+            Some(best) if val_loss >= best - self.min_delta => self.wait += 1,
+            _ => {
+                self.best = Some(val_loss);
+                self.best_parameters = net.parameters().map(|(_, &p)| p).collect();
+                self.wait = 0;
+            }
+        }
+        if self.wait >= self.patience {
+            Control::Stop
+        } else {
+            Control::Continue
+        }
+    }
+
+    fn on_train_end(&mut self, net: &mut NeuralNetwork<F, N, A>, _history: &History<F>) {
+        if self.restore_best {
+            for ((_, param), &best) in net.parameters_mut().zip(&self.best_parameters) {
+                *param = best;
+            }
+        }
+    }
+}
+
+/// Loss and gradient of a batch split across the threads of a pool, see [`Trainer::threads`]
+#[cfg(feature = "rayon")]
+type ParallelGradient<F, const N: usize, A, L> =
+    fn(&ThreadPool, &NeuralNetwork<F, N, A>, &Array2<F>, &Array2<F>, &L, Engine) -> (F, Vec<F>);
+
+/// Trains a [`NeuralNetwork`] on a [`Dataset`] by running the optimizer on every training batch
+///
+/// The dataset's batches are converted into the network's number type `F`.
+/// ```
+/// # use deep_thought::prelude::*;
+/// # use deep_thought::metrics::MAE;
+/// # use deep_thought::optimizer::*;
+/// # use ndarray::prelude::*;
+/// let inputs = array![[-1.], [0.], [1.], [2.]];
+/// let labels = array![[-1.], [1.], [3.], [5.]];
+/// let dataset = Dataset::raw(inputs, labels, 1., BatchSize::All).unwrap();
+///
+/// let net = NeuralNetwork::<f64, 2>::new().add_layer(Layer::new(1, 1));
+/// let mut trainer = Trainer::new(net, Loss::MSE, SGD::new().learning_rate(0.1))
+///     .metric("mae", MAE::new());
+/// let history = trainer.fit(&dataset, 200).unwrap();
+///
+/// assert_eq!(history.epochs.len(), 200);
+/// assert!(history.epochs[199].metric("mae").unwrap() < 1e-3);
+/// ```
+pub struct Trainer<F, const N: usize, A, L, O> {
+    net: NeuralNetwork<F, N, A>,
+    loss: L,
+    optimizer: O,
+    engine: Engine,
+    /// threads each batch is split across together with the function splitting it,
+    /// `None` if batches stay on the calling thread
+    #[cfg(feature = "rayon")]
+    parallel: Option<(ThreadPool, ParallelGradient<F, N, A, L>)>,
+    /// number of epochs between evaluations on the test data
+    validation_interval: usize,
+    metrics: Vec<(String, Box<dyn Metric<F>>)>,
+    callbacks: Vec<Box<dyn Callback<F, N, A>>>,
+    /// number of epochs trained over all calls to `fit`
+    epoch: usize,
+}
+
+impl<F, const N: usize, A, L, O> Trainer<F, N, A, L, O>
+where
+    F: 'static + Float,
+    A: ActivationFn<F, N>,
+    L: LossFn<F, N>,
+    O: Optimizer<F, N>,
+{
+    /// Create a trainer computing gradients with [`Engine::Forward`]
+    pub fn new(net: NeuralNetwork<F, N, A>, loss: L, optimizer: O) -> Self {
+        Trainer {
+            net,
+            loss,
+            optimizer,
+            engine: Engine::Forward,
+            #[cfg(feature = "rayon")]
+            parallel: None,
+            validation_interval: 1,
+            metrics: vec![],
+            callbacks: vec![],
+            epoch: 0,
+        }
+    }
+
+    /// Set the engine used to compute the gradients
+    pub fn engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    /// Evaluate the test data only every `interval` epochs, starting with epoch `interval - 1`.
+    /// The other epochs have no test loss or metrics.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero
+    pub fn validate_every(mut self, interval: usize) -> Self {
+        assert!(interval > 0, "validation interval must be positive");
+        self.validation_interval = interval;
+        self
+    }
+
+    /// Track a metric at the end of every epoch, accumulated over all batches of the dataset
+    pub fn metric<M: Metric<F> + 'static>(mut self, name: &str, metric: M) -> Self {
+        self.metrics.push((name.to_string(), Box::new(metric)));
+        self
+    }
+
+    /// Add a callback, callbacks are invoked in the order they were added
+    pub fn callback<C: Callback<F, N, A> + 'static>(mut self, callback: C) -> Self {
+        self.callbacks.push(Box::new(callback));
+        self
+    }
+
+    /// The trained network
+    pub fn net(&self) -> &NeuralNetwork<F, N, A> {
+        &self.net
+    }
+
+    /// The optimizer, e.g. to adjust its learning rate between calls to [`Trainer::fit`]
+    pub fn optimizer_mut(&mut self) -> &mut O {
+        &mut self.optimizer
+    }
+
+    /// Take the trained network out of the trainer
+    pub fn into_net(self) -> NeuralNetwork<F, N, A> {
+        self.net
+    }
+
+    /// Train for `epochs` epochs or until a callback stops training, and return the logs of every epoch.
+    ///
+    /// Calling `fit` again continues training where the previous call stopped. If a callback
+    /// stops training in the middle of an epoch, that epoch is still evaluated, logged and passed
+    /// to [`Callback::on_epoch_end`], with the loss averaged over the batches it trained on.
+    ///
+    /// Fails with [`Error::NoData`] if the dataset has no training batches.
+    pub fn fit(&mut self, dataset: &Dataset, epochs: usize) -> Result<History<F>> {
+        if dataset.iter_train().num_batches == 0 {
+            return Err(Error::NoData.into());
+        }
+
+        for callback in self.callbacks.iter_mut() {
+            callback.on_train_begin(&self.net);
+        }
+        let mut history = History { epochs: vec![] };
+        for _ in 0..epochs {
+            let epoch = self.epoch;
+            self.epoch += 1;
+
+            let mut total_loss = F::zero();
+            let mut num_batches = 0;
+            let mut stopped = false;
+            for (batch, (samples, labels)) in dataset.iter_train().enumerate() {
+                let (samples, labels) = (cast(samples), cast(labels));
+                let (loss, gradient) = self.loss_gradient(&samples, &labels);
+                self.optimizer.apply_gradient(&mut self.net, &gradient);
+                total_loss = total_loss + loss;
+                num_batches += 1;
+
+                let logs = BatchLogs { epoch, batch, loss };
+                if self.notify(|callback, net| callback.on_batch_end(net, &logs)) == Control::Stop {
+                    stopped = true;
+                    break;
+                }
+            }
+
+            let metrics = if self.metrics.is_empty() {
+                vec![]
+            } else {
+                self.evaluate(dataset.iter_train()).1
+            };
+            let validate = (epoch + 1).is_multiple_of(self.validation_interval);
+            let (val_loss, val_metrics) = if validate && dataset.iter_test().num_batches > 0 {
+                let (loss, metrics) = self.evaluate(dataset.iter_test());
+                (Some(loss), metrics)
+            } else {
+                (None, vec![])
+            };
+            let logs = EpochLogs {
+                epoch,
+                loss: total_loss / F::from(num_batches).unwrap(),
+                metrics,
+                val_loss,
+                val_metrics,
+            };
+            let control = self.notify(|callback, net| callback.on_epoch_end(net, &logs));
+            history.epochs.push(logs);
+            if stopped || control == Control::Stop {
+                break;
+            }
+        }
+
+        for callback in self.callbacks.iter_mut() {
+            callback.on_train_end(&mut self.net, &history);
+        }
+        Ok(history)
+    }
+
+    /// Loss and gradient of a training batch, split across threads if [`Trainer::threads`] is set
+    fn loss_gradient(&self, samples: &Array2<F>, labels: &Array2<F>) -> (F, Vec<F>) {
+        #[cfg(feature = "rayon")]
+        if let Some((pool, parallel)) = &self.parallel {
+            return parallel(pool, &self.net, samples, labels, &self.loss, self.engine);
+        }
+        self.net
+            .loss_gradient(samples, labels, &self.loss, self.engine)
+    }
+
+    /// Mean loss and metrics over all batches without changing the network.
+    /// `batches` must not be empty
+    fn evaluate(&mut self, batches: SampleIterator<'_>) -> (F, Vec<(String, F)>) {
+        let num_batches = batches.num_batches;
+        let mut total_loss = F::zero();
+        for (_, metric) in self.metrics.iter_mut() {
+            metric.reset();
+        }
+        for (samples, labels) in batches {
+            let labels = cast(labels);
+            let out = self.net.predict(&cast(samples));
+            total_loss = total_loss + self.loss.reduce(&out, &labels, Reduction::Mean);
+            for (_, metric) in self.metrics.iter_mut() {
+                metric.update(&out, &labels);
+            }
+        }
+        let metrics = self
+            .metrics
+            .iter()
+            .map(|(name, metric)| (name.clone(), metric.result()))
+            .collect();
+        (total_loss / F::from(num_batches).unwrap(), metrics)
+    }
+
+    /// Invoke every callback in order, stopping if any of them asks to
+    fn notify<G>(&mut self, mut f: G) -> Control
+    where
+        G: FnMut(&mut Box<dyn Callback<F, N, A>>, &NeuralNetwork<F, N, A>) -> Control,
+    {
+        let mut control = Control::Continue;
+        for callback in self.callbacks.iter_mut() {
+            if f(callback, &self.net) == Control::Stop {
+                control = Control::Stop;
+            }
+        }
+        control
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<F, const N: usize, A, L, O> Trainer<F, N, A, L, O>
+where
+    F: 'static + Float + Send + Sync,
+    A: ActivationFn<F, N> + Sync,
+    L: LossFn<F, N> + Sync,
+{
+    /// Split every training batch across `threads` threads, see [`NeuralNetwork::parallel_loss_gradient`].
+    /// Requires the `rayon` feature.
+    ///
+    /// The trainer starts its own pool of `threads` threads, which is kept for every batch
+    /// until the trainer is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the threads can not be started
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.parallel = if threads > 1 {
+            let pool = ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .expect("failed to start the training threads");
+            Some((pool, parallel_gradient::<F, N, A, L>))
+        } else {
+            None
+        };
+        self
+    }
+}
+
+/// Split a batch across the threads of `pool`
+#[cfg(feature = "rayon")]
+fn parallel_gradient<F, const N: usize, A, L>(
+    pool: &ThreadPool,
+    net: &NeuralNetwork<F, N, A>,
+    samples: &Array2<F>,
+    labels: &Array2<F>,
+    loss: &L,
+    engine: Engine,
+) -> (F, Vec<F>)
+where
+    F: 'static + Float + Send + Sync,
+    A: ActivationFn<F, N> + Sync,
+    L: LossFn<F, N> + Sync,
+{
+    let threads = pool.current_num_threads();
+    pool.install(|| net.parallel_loss_gradient(samples, labels, loss, engine, threads))
+}
+
+/// Convert a batch of the dataset into the network's number type
+fn cast<F: Float>(batch: Array2<f64>) -> Array2<F> {
+    batch.mapv(|x| F::from(x).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// y = 2x + 1, with the last two samples used for testing
+    fn dataset() -> Dataset {
+        let inputs = array![[-1.], [0.], [1.], [2.], [3.], [4.]];
+        let labels = inputs.mapv(|x| 2. * x + 1.);
+        Dataset::raw(inputs, labels, 0.7, BatchSize::Number(2)).unwrap()
+    }
+
+    fn trainer() -> Trainer<f64, 2, Activation<f64, 2>, Loss<f64>, optimizer::SGD<f64, 2>> {
+        let mut net = NeuralNetwork::new().add_layer(Layer::new(1, 1));
+        net.layers[0].W[[0, 0]] = 0.;
+        Trainer::new(net, Loss::MSE, optimizer::SGD::new().learning_rate(0.05))
+    }
+
+    /// Records every call it receives
+    struct Recorder {
+        calls: Rc<RefCell<Vec<String>>>,
+        stop_after: usize,
+        /// epoch and index of the batch after which training is stopped
+        stop_at_batch: Option<(usize, usize)>,
+    }
+
+    impl Callback<f64, 2, Activation<f64, 2>> for Recorder {
+        fn on_train_begin(&mut self, _: &NeuralNetwork<f64, 2>) {
+            self.calls.borrow_mut().push("begin".to_string());
+        }
+
+        fn on_batch_end(&mut self, _: &NeuralNetwork<f64, 2>, logs: &BatchLogs<f64>) -> Control {
+            let call = format!("batch {} {}", logs.epoch, logs.batch);
+            self.calls.borrow_mut().push(call);
+            if self.stop_at_batch == Some((logs.epoch, logs.batch)) {
+                Control::Stop
+            } else {
+                Control::Continue
+            }
+        }
+
+        fn on_epoch_end(&mut self, _: &NeuralNetwork<f64, 2>, logs: &EpochLogs<f64>) -> Control {
+            self.calls
+                .borrow_mut()
+                .push(format!("epoch {}", logs.epoch));
+            if logs.epoch + 1 == self.stop_after {
+                Control::Stop
+            } else {
+                Control::Continue
+            }
+        }
+
+        fn on_train_end(&mut self, _: &mut NeuralNetwork<f64, 2>, history: &History<f64>) {
+            let call = format!("end {}", history.epochs.len());
+            self.calls.borrow_mut().push(call);
+        }
+    }
+
+    #[test]
+    fn fit_records_history() {
+        let mut trainer = trainer().metric("r2", metrics::R2::new());
+        let history = trainer.fit(&dataset(), 300).unwrap();
+        assert_eq!(history.epochs.len(), 300);
+        assert_eq!(history.loss().len(), 300);
+        assert_eq!(history.val_loss().len(), 300);
+
+        let first = &history.epochs[0];
+        let last = &history.epochs[299];
+        assert!(last.loss < first.loss);
+        assert!(last.val_loss.unwrap() < 1e-3);
+        assert!(last.metric("r2").unwrap() > 0.99);
+        assert!(last.val_metric("r2").unwrap() > 0.99);
+        assert_eq!(last.metric("missing"), None);
+
+        // training continues where it stopped
+        let history = trainer.fit(&dataset(), 1).unwrap();
+        assert_eq!(history.epochs[0].epoch, 300);
+    }
+
+    #[test]
+    fn callbacks_can_stop_training() {
+        let calls = Rc::new(RefCell::new(vec![]));
+        let recorder = Recorder {
+            calls: calls.clone(),
+            stop_after: 2,
+            stop_at_batch: None,
+        };
+        let history = trainer().callback(recorder).fit(&dataset(), 10).unwrap();
+        assert_eq!(history.epochs.len(), 2);
+        assert_eq!(
+            *calls.borrow(),
+            [
+                "begin",
+                "batch 0 0",
+                "batch 0 1",
+                "epoch 0",
+                "batch 1 0",
+                "batch 1 1",
+                "epoch 1",
+                "end 2"
+            ]
+        );
+    }
+
+    #[test]
+    fn stopping_after_a_batch_finishes_the_epoch() {
+        let calls = Rc::new(RefCell::new(vec![]));
+        let recorder = Recorder {
+            calls: calls.clone(),
+            stop_after: 10,
+            stop_at_batch: Some((1, 0)),
+        };
+        let history = trainer().callback(recorder).fit(&dataset(), 10).unwrap();
+        assert_eq!(
+            *calls.borrow(),
+            [
+                "begin",
+                "batch 0 0",
+                "batch 0 1",
+                "epoch 0",
+                "batch 1 0",
+                "epoch 1",
+                "end 2"
+            ]
+        );
+
+        // the partial epoch is logged with the loss of its only batch
+        assert_eq!(history.epochs.len(), 2);
+        let partial = &history.epochs[1];
+        assert_eq!(partial.epoch, 1);
+        assert!(partial.val_loss.is_some());
+        let (samples, labels) = dataset().iter_train().next().unwrap();
+        let mut net = trainer().into_net();
+        let mut optim = optimizer::SGD::new().learning_rate(0.05);
+        for (samples, labels) in dataset().iter_train() {
+            let (_, gradient) = net.loss_gradient(&samples, &labels, &Loss::MSE, Engine::Forward);
+            optim.apply_gradient(&mut net, &gradient);
+        }
+        let (loss, _) = net.loss_gradient(&samples, &labels, &Loss::MSE, Engine::Forward);
+        assert!((partial.loss - loss).abs() < 1e-12);
+    }
+
+    /// Training data of y = 2x + 1, but test data of y = 0, so the test loss only gets worse
+    fn diverging_dataset() -> Dataset {
+        let inputs = array![[-1.], [0.], [1.], [2.], [0.1], [0.2]];
+        let labels = array![[-1.], [1.], [3.], [5.], [0.], [0.]];
+        Dataset::raw(inputs, labels, 0.7, BatchSize::All).unwrap()
+    }
+
+    #[test]
+    fn validate_every() {
+        let history = trainer().validate_every(3).fit(&dataset(), 7).unwrap();
+        let validated: Vec<_> = history
+            .epochs
+            .iter()
+            .map(|e| e.val_loss.is_some())
+            .collect();
+        assert_eq!(validated, [false, false, true, false, false, true, false]);
+    }
+
+    #[test]
+    #[should_panic(expected = "validation interval must be positive")]
+    fn validate_every_requires_interval() {
+        trainer().validate_every(0);
+    }
+
+    #[test]
+    fn early_stopping_restores_best_parameters() {
+        let mut trainer = trainer().callback(EarlyStopping::new(3));
+        let history = trainer.fit(&diverging_dataset(), 100).unwrap();
+        assert_eq!(history.epochs.len(), 4);
+        assert!(history.val_loss().windows(2).all(|w| w[0] < w[1]));
+
+        // the first epoch had the lowest test loss
+        let mut expected = self::trainer();
+        expected.fit(&diverging_dataset(), 1).unwrap();
+        assert_eq!(trainer.net().layers[0].W, expected.net().layers[0].W);
+        assert_eq!(trainer.net().layers[0].B, expected.net().layers[0].B);
+
+        // keep the last parameters
+        let stopping = EarlyStopping::new(3).restore_best(false);
+        let mut trainer = self::trainer().callback(stopping);
+        trainer.fit(&diverging_dataset(), 100).unwrap();
+        let mut expected = self::trainer();
+        expected.fit(&diverging_dataset(), 4).unwrap();
+        assert_eq!(trainer.net().layers[0].W, expected.net().layers[0].W);
+
+        // another call does not compare against the test losses of the previous one
+        let history = trainer.fit(&diverging_dataset(), 100).unwrap();
+        assert_eq!(history.epochs.len(), 4);
+    }
+
+    #[test]
+    fn fit_requires_training_batches() {
+        let inputs = array![[-1.], [0.], [1.]];
+        let labels = inputs.mapv(|x| 2. * x + 1.);
+        let dataset = Dataset::raw(inputs, labels, 1., BatchSize::Number(4)).unwrap();
+        let err = trainer().fit(&dataset, 1).unwrap_err();
+        assert!(matches!(err.downcast_ref::<Error>(), Some(Error::NoData)));
+    }
+
+    #[test]
+    #[should_panic(expected = "logging interval must be positive")]
+    fn logger_requires_interval() {
+        Logger::new(0);
+    }
+
+    #[test]
+    fn trains_single_precision_networks() {
+        let mut net = NeuralNetwork::<f32, 2>::new().add_layer(Layer::new(1, 1));
+        net.layers[0].W[[0, 0]] = 0.;
+        let optimizer = optimizer::SGD::new().learning_rate(0.05);
+        let mut trainer = Trainer::new(net, Loss::MSE, optimizer)
+            .metric("r2", metrics::R2::new())
+            .callback(EarlyStopping::new(10));
+        let history = trainer.fit(&dataset(), 300).unwrap();
+        let last = history.epochs.last().unwrap();
+        assert!(last.val_loss.unwrap() < 1e-3f32);
+        assert!(last.metric("r2").unwrap() > 0.99f32);
+    }
+
+    #[test]
+    fn engines_train_identically() {
+        let forward = trainer().fit(&dataset(), 5).unwrap();
+        let reverse = trainer()
+            .engine(Engine::Reverse)
+            .fit(&dataset(), 5)
+            .unwrap();
+        for (a, b) in forward.epochs.iter().zip(&reverse.epochs) {
+            assert!((a.loss - b.loss).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn trains_with_activations_shared_between_layers() {
+        /// An activation that is not `Sync`, so it can only be trained on one thread
+        struct Shared(Rc<Activation<f64, 2>>);
+
+        impl<D: Float + From<f64>> ActivationOn<f64, D> for Shared {
+            fn compute(&self, inp: &Array2<D>) -> Array2<D> {
+                self.0.compute(inp)
+            }
+        }
+
+        let mut net = NeuralNetwork::<f64, 2, _>::new()
+            .add_layer(Layer::new(1, 1).activation(Shared(Rc::new(Activation::Linear))));
+        net.layers[0].W[[0, 0]] = 0.;
+        let mut trainer = Trainer::new(net, Loss::MSE, optimizer::SGD::new().learning_rate(0.05));
+        let shared = trainer.fit(&dataset(), 5).unwrap();
+        let expected = self::trainer().fit(&dataset(), 5).unwrap();
+        assert_eq!(shared.loss(), expected.loss());
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn threads_train_like_a_single_thread() {
+        let single = trainer().fit(&dataset(), 5).unwrap();
+        let parallel = trainer().threads(3).fit(&dataset(), 5).unwrap();
+        for (a, b) in single.epochs.iter().zip(&parallel.epochs) {
+            assert!((a.loss - b.loss).abs() < 1e-12);
+        }
+    }
+}
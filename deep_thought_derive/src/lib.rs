@@ -8,9 +8,10 @@ use syn::{
     spanned::Spanned,
     Expr,
     Expr::{Call, MethodCall},
-    Ident, Lit, Token, Type, Visibility,
+    Ident, Lit, Token, Type,
 };
 
+#[allow(dead_code)] // only `init` is inspected, the rest is parsed for validation
 struct Network {
     name: Ident,
     ty: Type,
@@ -20,6 +21,7 @@ struct Network {
 impl Parse for Network {
     fn parse(input: ParseStream) -> Result<Self> {
         input.parse::<Token![let]>()?;
+        input.parse::<Option<Token![mut]>>()?;
         let name: Ident = input.parse()?;
         input.parse::<Token![:]>()?;
         let ty: Type = input.parse()?;
@@ -61,11 +63,7 @@ macro_rules! int_lit_from_fn_arg {
 pub fn neural_network(input: TokenStream) -> TokenStream {
     // Parse the TokenStream into an Abstract Syntax Tree (AST)
     let cloned_inp = input.clone();
-    let Network {
-        name,
-        ty,
-        init,
-    } = parse_macro_input!(cloned_inp as Network);
+    let Network { init, .. } = parse_macro_input!(cloned_inp as Network);
 
     let mut num_parameters = 0;
     let mut fn_ref = &init;